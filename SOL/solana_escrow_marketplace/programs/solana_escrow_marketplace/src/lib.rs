@@ -1,329 +1,4114 @@
+// anchor-lang 0.31 `#[program]` expands to the deprecated `AccountInfo::realloc` outside the program
+// module, where an item-level allow can't reach it
+#![allow(deprecated)]
+
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hashv;
 use anchor_lang::system_program::{Transfer, transfer};
+use anchor_spl::associated_token::{get_associated_token_address_with_program_id, AssociatedToken};
+use anchor_spl::memo::{build_memo, BuildMemo, Memo};
+use anchor_spl::token_2022::spl_token_2022::extension::{
+    memo_transfer::memo_required, permanent_delegate::PermanentDelegate, transfer_fee::TransferFeeAmount,
+    BaseStateWithExtensions, StateWithExtensions,
+};
+use anchor_spl::token_2022::spl_token_2022::state::{Account as SplTokenAccount, Mint as SplMint};
+use anchor_spl::token_2022_extensions::{harvest_withheld_tokens_to_mint, HarvestWithheldTokensToMint};
+use anchor_spl::token_interface::{
+    burn, close_account, transfer_checked, Burn, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
 
 // Program ID - will be updated after deployment
 declare_id!("5bCqmbtwBZSvorHtu8PtsFPWoL1drC8Ps7vD5DgwqPPa"); 
 
-// YOUR PHANTOM WALLET ADDRESSES
-// Authority wallet that controls the marketplace and escrow decisions
-const MARKETPLACE_AUTHORITY_PUBKEY_STR: &str = "57CEpYPybCqQiLmvS5oUUZbdUVrvYtaYPJW24SgyEcuT";
-// Fee collection wallet (same as authority)
-const MARKETPLACE_FEE_WALLET_PUBKEY_STR: &str = "57CEpYPybCqQiLmvS5oUUZbdUVrvYtaYPJW24SgyEcuT";
+// Pause flags for `set_paused`. Refunds are never pausable so buyers can always exit.
+#[constant]
+pub const PAUSE_CREATE: u16 = 1 << 0;      // initialize_escrow, initialize_token_escrow
+#[constant]
+pub const PAUSE_RELEASE: u16 = 1 << 1;     // every path paying the seller
+#[constant]
+pub const PAUSE_DISTRIBUTION: u16 = 1 << 2; // distribute_fees, post_airdrop_epoch
+#[constant]
+pub const PAUSE_ALL: u16 = PAUSE_CREATE | PAUSE_RELEASE | PAUSE_DISTRIBUTION;
+
+// Hard cap on any configured fee rate (10%)
+const MAX_FEE_BASIS_POINTS: u16 = 1000;
+// Default time the seller has to ship before the buyer can be refunded (14 days)
+const DEFAULT_DELIVERY_PERIOD: i64 = 14 * 24 * 60 * 60;
+// Default time the buyer has to confirm or dispute before funds auto-release (7 days)
+const DEFAULT_CONFIRMATION_PERIOD: i64 = 7 * 24 * 60 * 60;
+// Maximum evidence hashes each party can store per dispute
+const MAX_DISPUTE_EVIDENCE_PER_PARTY: usize = 5;
+// Default buyer and seller fee rates (1% each, per the published fee model)
+const DEFAULT_BUYER_FEE_BASIS_POINTS: u16 = 100;
+const DEFAULT_SELLER_FEE_BASIS_POINTS: u16 = 100;
+// Default share of the buyer fee waived for $SZ holders (20%)
+const DEFAULT_HOLDER_DISCOUNT_BASIS_POINTS: u16 = 2000;
+// Maximum amount tiers in the fee schedule
+const MAX_FEE_TIERS: usize = 8;
+// $SZ the buyback-and-burn program aims to remove from supply, in whole tokens
+const BURN_TARGET_TOKENS: u64 = 100_000_000;
+// Maximum claimants per airdrop epoch, bounded by the claimed bitmap's account size
+const MAX_AIRDROP_CLAIMANTS: u32 = 64_000;
+// Default fee revenue split: 10% burn, 40% holder airdrops, 45% operations, 5% development
+const DEFAULT_FEE_DISTRIBUTION: FeeDistribution = FeeDistribution {
+    burn_basis_points: 1000,
+    airdrop_basis_points: 4000,
+    operations_basis_points: 4500,
+    development_basis_points: 500,
+};
 
 #[program]
 pub mod solana_escrow_marketplace {
     use super::*;
 
-    /// Initializes a new escrow.
-    /// The buyer deposits funds, marketplace takes a fee, rest is held for seller.
-    /// Only the pre-defined marketplace authority can later release or cancel.
-    pub fn initialize_escrow(
-        ctx: Context<InitializeEscrow>,
-        transaction_seed: u64, 
-        total_amount_to_escrow: u64, 
-        fee_basis_points: u16, 
+    /// Creates the singleton marketplace config and funds the fee vault's rent-exempt minimum.
+    /// Only the program's upgrade authority can call this, so the config can't be squatted after deploy.
+    /// All fee shares go to `fee_wallet` until `set_fee_distribution` configures the other wallets.
+    pub fn initialize_config(
+        ctx: Context<InitializeConfig>,
+        authority: Pubkey,
+        fee_wallet: Pubkey,
+        max_fee_basis_points: u16,
+        min_escrow_amount: u64,
+        min_net_amount: u64,
+        escrow_retention_period: i64,
     ) -> Result<()> {
-        // --- Enhanced Validation ---
-        require!(total_amount_to_escrow > 0, EscrowError::ZeroAmount);
-        require!(fee_basis_points > 0 && fee_basis_points <= 1000, EscrowError::InvalidFeeBasisPoints); // Max 10%
-        require!(total_amount_to_escrow >= 1_000_000, EscrowError::MinimumAmount); // Min 0.001 SOL
+        validate_config_params(max_fee_basis_points, min_escrow_amount, min_net_amount, escrow_retention_period)?;
 
-        let escrow_state = &mut ctx.accounts.escrow_state;
-        let buyer = &ctx.accounts.buyer;
-        let seller = &ctx.accounts.seller; 
+        let config = &mut ctx.accounts.config;
+        config.authority = authority;
+        config.fee_wallet = fee_wallet;
+        config.max_fee_basis_points = max_fee_basis_points;
+        config.min_escrow_amount = min_escrow_amount;
+        config.min_net_amount = min_net_amount;
+        config.escrow_retention_period = escrow_retention_period;
+        // Rates can never exceed the configured cap
+        config.buyer_fee_basis_points = DEFAULT_BUYER_FEE_BASIS_POINTS.min(max_fee_basis_points);
+        config.seller_fee_basis_points = DEFAULT_SELLER_FEE_BASIS_POINTS.min(max_fee_basis_points);
+        // Holder discount stays off until the $SZ mint is configured
+        config.discount_mint = None;
+        config.discount_threshold = 0;
+        config.holder_discount_basis_points = DEFAULT_HOLDER_DISCOUNT_BASIS_POINTS;
+        config.pending_authority = None;
+        config.arbiter = authority;
+        // Permanent delegates can pull tokens out of the escrow vault, so reject them unless opted in
+        config.reject_permanent_delegate = true;
+        config.delivery_period = DEFAULT_DELIVERY_PERIOD;
+        config.confirmation_period = DEFAULT_CONFIRMATION_PERIOD;
+        config.burn_wallet = fee_wallet;
+        config.airdrop_wallet = fee_wallet;
+        config.development_wallet = fee_wallet;
+        config.fee_distribution = DEFAULT_FEE_DISTRIBUTION;
+        config.fee_vault_bump = ctx.bumps.fee_vault;
+        config.total_burned = 0;
+        config.paused_instructions = 0;
+        // Burns stay off until the $SZ mint is configured
+        config.sz_mint = None;
+        config.airdrop_drawn = 0;
+        config.bump = ctx.bumps.config;
 
-        // --- Validate Authority ---
-        let expected_authority = MARKETPLACE_AUTHORITY_PUBKEY_STR.parse::<Pubkey>()
-            .map_err(|_| EscrowError::InvalidAuthorityAddress)?;
-        require_keys_eq!(ctx.accounts.marketplace_authority.key(), expected_authority, EscrowError::UnauthorizedAuthority);
+        // --- Fund Fee Vault Rent ---
+        // Fees can be smaller than the rent-exempt minimum, so the vault must already hold it
+        let fee_vault_rent = Rent::get()?.minimum_balance(0)
+            .saturating_sub(ctx.accounts.fee_vault.lamports());
+        if fee_vault_rent > 0 {
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.payer.to_account_info(),
+                to: ctx.accounts.fee_vault.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(ctx.accounts.system_program.to_account_info(), cpi_accounts);
+            transfer(cpi_ctx, fee_vault_rent)?;
+        }
 
-        // --- Fee Calculation with Safety ---
-        let fee_amount = total_amount_to_escrow
-            .checked_mul(fee_basis_points as u64)
-            .and_then(|x| x.checked_div(10000))
-            .ok_or(EscrowError::ArithmeticOverflow)?;
-        
-        require!(fee_amount > 0, EscrowError::FeeTooSmall); 
-        require!(total_amount_to_escrow > fee_amount, EscrowError::AmountLessThanFee);
-        
-        let amount_for_seller = total_amount_to_escrow.checked_sub(fee_amount)
-            .ok_or(EscrowError::ArithmeticOverflow)?;
+        emit!(ConfigUpdated {
+            authority: config.authority,
+            fee_wallet: config.fee_wallet,
+            max_fee_basis_points: config.max_fee_basis_points,
+            min_escrow_amount: config.min_escrow_amount,
+            min_net_amount: config.min_net_amount,
+            escrow_retention_period: config.escrow_retention_period,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
 
-        // --- Validate Minimum Net Amount ---
-        require!(amount_for_seller >= 500_000, EscrowError::NetAmountTooSmall); // Min 0.0005 SOL after fee
+        msg!("✅ Marketplace config initialized - Authority: {}, Fee wallet: {}", 
+            config.authority, config.fee_wallet);
+        Ok(())
+    }
 
-        // --- SOL Transfers ---
-        // 1. Transfer total amount from buyer to escrow PDA
-        let cpi_accounts_buyer_to_escrow = Transfer {
-            from: buyer.to_account_info(),
-            to: escrow_state.to_account_info(),
-        };
-        let cpi_program_buyer_to_escrow = ctx.accounts.system_program.to_account_info();
-        let cpi_ctx_buyer_to_escrow = CpiContext::new(cpi_program_buyer_to_escrow, cpi_accounts_buyer_to_escrow);
-        transfer(cpi_ctx_buyer_to_escrow, total_amount_to_escrow)?;
+    /// Updates the operations fee wallet and escrow limits - only callable by marketplace authority
+    pub fn update_config(
+        ctx: Context<UpdateConfig>,
+        fee_wallet: Pubkey,
+        max_fee_basis_points: u16,
+        min_escrow_amount: u64,
+        min_net_amount: u64,
+        escrow_retention_period: i64,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        require_keys_eq!(ctx.accounts.authority.key(), config.authority, EscrowError::Unauthorized);
+        validate_config_params(max_fee_basis_points, min_escrow_amount, min_net_amount, escrow_retention_period)?;
+        require!(
+            config.buyer_fee_basis_points <= max_fee_basis_points && config.seller_fee_basis_points <= max_fee_basis_points,
+            EscrowError::InvalidFeeBasisPoints
+        );
 
-        // Reload account to reflect new balance
-        escrow_state.reload()?;
+        config.fee_wallet = fee_wallet;
+        config.max_fee_basis_points = max_fee_basis_points;
+        config.min_escrow_amount = min_escrow_amount;
+        config.min_net_amount = min_net_amount;
+        config.escrow_retention_period = escrow_retention_period;
 
-        // 2. Transfer fee from escrow PDA to marketplace fee wallet
-        let marketplace_fee_wallet_pubkey = MARKETPLACE_FEE_WALLET_PUBKEY_STR.parse::<Pubkey>()
-            .map_err(|_| EscrowError::InvalidFeeWalletAddress)?;
-        require_keys_eq!(ctx.accounts.marketplace_fee_wallet.key(), marketplace_fee_wallet_pubkey, EscrowError::IncorrectFeeWallet);
+        emit!(ConfigUpdated {
+            authority: config.authority,
+            fee_wallet: config.fee_wallet,
+            max_fee_basis_points: config.max_fee_basis_points,
+            min_escrow_amount: config.min_escrow_amount,
+            min_net_amount: config.min_net_amount,
+            escrow_retention_period: config.escrow_retention_period,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
 
-        let cpi_accounts_escrow_to_fee = Transfer {
-            from: escrow_state.to_account_info(),
-            to: ctx.accounts.marketplace_fee_wallet.to_account_info(),
-        };
-        
-        // PDA signing seeds
-        let bump_seed = escrow_state.bump;
-        let seeds = &[
-            b"escrow".as_ref(),
-            &transaction_seed.to_le_bytes(),
-            &[bump_seed],
-        ];
-        let signer_seeds = &[&seeds[..]];
-        
-        let cpi_program_escrow_to_fee = ctx.accounts.system_program.to_account_info();
-        let cpi_ctx_escrow_to_fee = CpiContext::new_with_signer(
-            cpi_program_escrow_to_fee,
-            cpi_accounts_escrow_to_fee,
-            signer_seeds
+        msg!("✅ Marketplace config updated - Fee wallet: {}, Max fee bps: {}", 
+            config.fee_wallet, config.max_fee_basis_points);
+        Ok(())
+    }
+
+    /// Sets the buyer and seller fee rates applied to new escrows - only callable by marketplace authority.
+    /// The buyer fee is charged on top of the deposit; the seller fee is deducted at release.
+    pub fn set_fee_rates(
+        ctx: Context<UpdateConfig>,
+        buyer_fee_basis_points: u16,
+        seller_fee_basis_points: u16,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        require_keys_eq!(ctx.accounts.authority.key(), config.authority, EscrowError::Unauthorized);
+        require!(
+            buyer_fee_basis_points <= config.max_fee_basis_points && seller_fee_basis_points <= config.max_fee_basis_points,
+            EscrowError::InvalidFeeBasisPoints
         );
-        transfer(cpi_ctx_escrow_to_fee, fee_amount)?;
-        
-        // --- Initialize Escrow State ---
-        escrow_state.buyer = buyer.key();
-        escrow_state.seller = seller.key();
-        escrow_state.marketplace_authority = expected_authority;
-        escrow_state.total_initial_amount = total_amount_to_escrow;
-        escrow_state.fee_amount = fee_amount;
-        escrow_state.amount_for_seller = amount_for_seller;
-        escrow_state.stage = EscrowStage::Funded;
-        escrow_state.is_initialized = true;
-        escrow_state.created_at = Clock::get()?.unix_timestamp;
 
-        emit!(EscrowCreated {
-            escrow_id: escrow_state.key(),
-            buyer: buyer.key(),
-            seller: seller.key(),
-            amount: amount_for_seller,
-            fee: fee_amount,
-            timestamp: escrow_state.created_at,
+        config.buyer_fee_basis_points = buyer_fee_basis_points;
+        config.seller_fee_basis_points = seller_fee_basis_points;
+
+        emit!(FeeRatesUpdated {
+            buyer_fee_basis_points,
+            seller_fee_basis_points,
+            timestamp: Clock::get()?.unix_timestamp,
         });
 
-        msg!("✅ Escrow initialized - Buyer: {}, Seller: {}, Amount: {}, Fee: {}", 
-            escrow_state.buyer, escrow_state.seller, amount_for_seller, fee_amount);
+        msg!("✅ Fee rates updated - Buyer: {} bps, Seller: {} bps", buyer_fee_basis_points, seller_fee_basis_points);
         Ok(())
     }
 
-    /// Releases funds to seller - only callable by marketplace authority
-    pub fn release_funds_to_seller(ctx: Context<ProcessEscrow>, transaction_seed: u64) -> Result<()> {
-        let escrow_state = &mut ctx.accounts.escrow_state;
-        let caller = &ctx.accounts.caller; 
+    /// Configures the $SZ holder discount - only callable by marketplace authority.
+    /// Buyers holding at least `discount_threshold` of `discount_mint` get `holder_discount_basis_points`
+    /// of the buyer fee waived. Passing `None` as the mint turns the discount off.
+    pub fn set_holder_discount(
+        ctx: Context<UpdateConfig>,
+        discount_mint: Option<Pubkey>,
+        discount_threshold: u64,
+        holder_discount_basis_points: u16,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        require_keys_eq!(ctx.accounts.authority.key(), config.authority, EscrowError::Unauthorized);
+        require!(holder_discount_basis_points <= 10000, EscrowError::InvalidDiscount);
 
-        // --- Strict Authorization ---
-        let expected_authority = MARKETPLACE_AUTHORITY_PUBKEY_STR.parse::<Pubkey>()
-            .map_err(|_| EscrowError::InvalidAuthorityAddress)?;
-        require_keys_eq!(caller.key(), expected_authority, EscrowError::Unauthorized);
-        require_keys_eq!(caller.key(), escrow_state.marketplace_authority, EscrowError::Unauthorized);
+        config.discount_mint = discount_mint;
+        config.discount_threshold = discount_threshold;
+        config.holder_discount_basis_points = holder_discount_basis_points;
 
-        // --- State Validation ---
-        require!(escrow_state.is_initialized, EscrowError::NotInitialized);
-        require!(escrow_state.stage == EscrowStage::Funded, EscrowError::AlreadyProcessedOrNotFunded);
-        require_keys_eq!(ctx.accounts.recipient_account.key(), escrow_state.seller, EscrowError::RecipientNotSeller);
+        emit!(HolderDiscountUpdated {
+            discount_mint,
+            discount_threshold,
+            holder_discount_basis_points,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
 
-        let amount_to_transfer = escrow_state.amount_for_seller;
-        require!(amount_to_transfer > 0, EscrowError::ZeroAmount);
+        msg!("✅ Holder discount updated - Mint: {:?}, Threshold: {}, Discount: {} bps", 
+            discount_mint, discount_threshold, holder_discount_basis_points);
+        Ok(())
+    }
 
-        // --- Transfer to Seller ---
-        let cpi_accounts = Transfer {
-            from: escrow_state.to_account_info(),
-            to: ctx.accounts.recipient_account.to_account_info(), 
-        };
-        let bump_seed = escrow_state.bump;
-        let seeds = &[
-            b"escrow".as_ref(),
-            &transaction_seed.to_le_bytes(),
-            &[bump_seed],
+    /// Replaces the amount tiers of the fee schedule - only callable by marketplace authority.
+    /// Tiers must be sorted by ascending `min_amount` (lamports); SOL escrows use the highest tier
+    /// they reach. A tier can only lower the config rates, never raise them.
+    pub fn set_fee_tiers(ctx: Context<SetFeeTiers>, tiers: Vec<FeeTier>) -> Result<()> {
+        let config = &ctx.accounts.config;
+        require_keys_eq!(ctx.accounts.authority.key(), config.authority, EscrowError::Unauthorized);
+        require!(tiers.len() <= MAX_FEE_TIERS, EscrowError::InvalidFeeTiers);
+        require!(
+            tiers.windows(2).all(|pair| pair[0].min_amount < pair[1].min_amount),
+            EscrowError::InvalidFeeTiers
+        );
+        require!(
+            tiers.iter().all(|tier| tier.buyer_fee_basis_points <= config.max_fee_basis_points
+                && tier.seller_fee_basis_points <= config.max_fee_basis_points),
+            EscrowError::InvalidFeeBasisPoints
+        );
+
+        let fee_schedule = &mut ctx.accounts.fee_schedule;
+        fee_schedule.tiers = tiers;
+        fee_schedule.bump = ctx.bumps.fee_schedule;
+
+        emit!(FeeTiersUpdated {
+            tiers: fee_schedule.tiers.clone(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("✅ Fee tiers updated - Tiers: {}", fee_schedule.tiers.len());
+        Ok(())
+    }
+
+    /// Sets per-seller fee rates for new escrows with `seller` - only callable by marketplace authority.
+    /// Like tiers, an override can only lower the rates that would otherwise apply.
+    pub fn set_seller_fee_override(
+        ctx: Context<SetSellerFeeOverride>,
+        seller: Pubkey,
+        buyer_fee_basis_points: u16,
+        seller_fee_basis_points: u16,
+    ) -> Result<()> {
+        let config = &ctx.accounts.config;
+        require_keys_eq!(ctx.accounts.authority.key(), config.authority, EscrowError::Unauthorized);
+        require!(
+            buyer_fee_basis_points <= config.max_fee_basis_points && seller_fee_basis_points <= config.max_fee_basis_points,
+            EscrowError::InvalidFeeBasisPoints
+        );
+
+        let seller_fee_override = &mut ctx.accounts.seller_fee_override;
+        seller_fee_override.seller = seller;
+        seller_fee_override.buyer_fee_basis_points = buyer_fee_basis_points;
+        seller_fee_override.seller_fee_basis_points = seller_fee_basis_points;
+        seller_fee_override.bump = ctx.bumps.seller_fee_override;
+
+        emit!(SellerFeeOverrideSet {
+            seller,
+            buyer_fee_basis_points,
+            seller_fee_basis_points,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("✅ Seller fee override set - Seller: {}, Buyer: {} bps, Seller: {} bps", 
+            seller, buyer_fee_basis_points, seller_fee_basis_points);
+        Ok(())
+    }
+
+    /// Removes a seller's fee override and returns its rent - only callable by marketplace authority
+    pub fn remove_seller_fee_override(ctx: Context<RemoveSellerFeeOverride>, seller: Pubkey) -> Result<()> {
+        require_keys_eq!(ctx.accounts.authority.key(), ctx.accounts.config.authority, EscrowError::Unauthorized);
+
+        emit!(SellerFeeOverrideRemoved {
+            seller,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("✅ Seller fee override removed - Seller: {}", seller);
+        Ok(())
+    }
+
+    /// Sets where distributed fees go and in what ratios - only callable by marketplace authority.
+    /// The operations share goes to the config's `fee_wallet`.
+    pub fn set_fee_distribution(
+        ctx: Context<UpdateConfig>,
+        burn_wallet: Pubkey,
+        airdrop_wallet: Pubkey,
+        development_wallet: Pubkey,
+        fee_distribution: FeeDistribution,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        require_keys_eq!(ctx.accounts.authority.key(), config.authority, EscrowError::Unauthorized);
+        fee_distribution.validate()?;
+
+        config.burn_wallet = burn_wallet;
+        config.airdrop_wallet = airdrop_wallet;
+        config.development_wallet = development_wallet;
+        config.fee_distribution = fee_distribution;
+
+        emit!(FeeDistributionUpdated {
+            burn_wallet,
+            airdrop_wallet,
+            operations_wallet: config.fee_wallet,
+            development_wallet,
+            fee_distribution,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("✅ Fee distribution updated - Burn: {} bps, Airdrop: {} bps, Operations: {} bps, Development: {} bps", 
+            fee_distribution.burn_basis_points, fee_distribution.airdrop_basis_points,
+            fee_distribution.operations_basis_points, fee_distribution.development_basis_points);
+        Ok(())
+    }
+
+    /// Splits the fee vault balance between the burn, airdrop, operations and development
+    /// wallets by the configured ratios - callable by anyone, since destinations are fixed by config.
+    /// Airdrop epochs posted since the last distribution count toward the SOL airdrop share.
+    /// Pass the token accounts to distribute a token's fees instead of lamports.
+    pub fn distribute_fees(ctx: Context<DistributeFees>) -> Result<()> {
+        let accounts = &ctx.accounts;
+        let config = &accounts.config;
+        config.check_not_paused(PAUSE_DISTRIBUTION)?;
+        let fee_vault_seeds: &[&[u8]] = &[b"fee_vault".as_ref(), &[config.fee_vault_bump]];
+        let signer_seeds = &[fee_vault_seeds];
+        let wallets = [
+            &accounts.burn_wallet,
+            &accounts.airdrop_wallet,
+            &accounts.operations_wallet,
+            &accounts.development_wallet,
         ];
-        let signer_seeds = &[&seeds[..]];
-        let cpi_program = ctx.accounts.system_program.to_account_info();
-        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
-        transfer(cpi_ctx, amount_to_transfer)?;
+
+        let (mint_key, total, shares) = match &accounts.mint {
+            // --- SOL Fees ---
+            None => {
+                // The vault keeps its rent-exempt minimum. Lamports already drawn into airdrop epochs
+                // were part of this undistributed balance, so they're split along with it.
+                let available = accounts.fee_vault.lamports()
+                    .saturating_sub(Rent::get()?.minimum_balance(0));
+                let total = available.checked_add(config.airdrop_drawn)
+                    .ok_or(EscrowError::ArithmeticOverflow)?;
+                require!(total > 0, EscrowError::NothingToDistribute);
+                let shares = config.fee_distribution.split(total)?;
+
+                // Epochs already paid part of the airdrop share; if the ratios changed since and they
+                // took more than it, operations covers the difference so the payout matches the vault
+                let mut payouts = shares;
+                payouts[1] = shares[1].saturating_sub(config.airdrop_drawn);
+                let overdrawn = config.airdrop_drawn.saturating_sub(shares[1]);
+                payouts[2] = shares[2].checked_sub(overdrawn).ok_or(EscrowError::ArithmeticOverflow)?;
+
+                for (wallet, share) in wallets.iter().zip(payouts) {
+                    if share == 0 {
+                        continue;
+                    }
+                    let cpi_accounts = Transfer {
+                        from: accounts.fee_vault.to_account_info(),
+                        to: wallet.to_account_info(),
+                    };
+                    let cpi_ctx = CpiContext::new_with_signer(accounts.system_program.to_account_info(), cpi_accounts, signer_seeds);
+                    transfer(cpi_ctx, share)?;
+                }
+                (None, total, shares)
+            }
+            // --- Token Fees ---
+            Some(mint) => {
+                let (fee_vault_token_account, token_program) =
+                    match (&accounts.fee_vault_token_account, &accounts.token_program) {
+                        (Some(fee_vault_token_account), Some(token_program)) => (fee_vault_token_account, token_program),
+                        _ => return err!(EscrowError::MissingTokenAccounts),
+                    };
+                require_keys_eq!(fee_vault_token_account.mint, mint.key(), EscrowError::MintMismatch);
+                require_keys_eq!(fee_vault_token_account.owner, accounts.fee_vault.key(), EscrowError::InvalidVaultTokenAccount);
+                let total = fee_vault_token_account.amount;
+                require!(total > 0, EscrowError::NothingToDistribute);
+                let shares = config.fee_distribution.split(total)?;
+
+                let destinations = [
+                    &accounts.burn_token_account,
+                    &accounts.airdrop_token_account,
+                    &accounts.operations_token_account,
+                    &accounts.development_token_account,
+                ];
+                for ((wallet, destination), share) in wallets.iter().zip(destinations).zip(shares) {
+                    if share == 0 {
+                        continue;
+                    }
+                    let destination = destination.as_ref().ok_or(EscrowError::MissingTokenAccounts)?;
+                    require_keys_eq!(destination.mint, mint.key(), EscrowError::MintMismatch);
+                    require_keys_eq!(destination.owner, wallet.key(), EscrowError::InvalidRecipientTokenAccount);
+
+                    memo_if_required(&destination.to_account_info(), accounts.memo_program.as_ref(), accounts.fee_vault.key())?;
+                    let cpi_accounts = TransferChecked {
+                        from: fee_vault_token_account.to_account_info(),
+                        mint: mint.to_account_info(),
+                        to: destination.to_account_info(),
+                        authority: accounts.fee_vault.to_account_info(),
+                    };
+                    let cpi_ctx = CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, signer_seeds);
+                    transfer_checked(cpi_ctx, share, mint.decimals)?;
+                }
+                (Some(mint.key()), total, shares)
+            }
+        };
+
+        if mint_key.is_none() {
+            ctx.accounts.config.airdrop_drawn = 0;
+        }
+
+        emit!(FeesDistributed {
+            mint: mint_key,
+            total,
+            burn_amount: shares[0],
+            airdrop_amount: shares[1],
+            operations_amount: shares[2],
+            development_amount: shares[3],
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("✅ Fees distributed - Mint: {:?}, Total: {}, Burn: {}, Airdrop: {}, Operations: {}, Development: {}", 
+            mint_key, total, shares[0], shares[1], shares[2], shares[3]);
+        Ok(())
+    }
+
+    /// Sets the $SZ mint that `burn_tokens` accepts - only callable by marketplace authority.
+    /// Independent of the holder discount mint; passing `None` turns burns off.
+    pub fn set_sz_mint(ctx: Context<UpdateConfig>, sz_mint: Option<Pubkey>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        require_keys_eq!(ctx.accounts.authority.key(), config.authority, EscrowError::Unauthorized);
+
+        config.sz_mint = sz_mint;
+
+        emit!(SzMintUpdated {
+            sz_mint,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("✅ $SZ mint updated - Mint: {:?}", sz_mint);
+        Ok(())
+    }
+
+    /// Burns $SZ bought back with the burn share of distributed fees - only callable by the burn wallet.
+    /// Adds to the cumulative burned counter so progress toward the 100M target is verifiable on-chain.
+    pub fn burn_tokens(ctx: Context<BurnTokens>, amount: u64) -> Result<()> {
+        let config = &ctx.accounts.config;
+        require_keys_eq!(ctx.accounts.burner.key(), config.burn_wallet, EscrowError::Unauthorized);
+        require!(amount > 0, EscrowError::ZeroAmount);
+        let mint = &ctx.accounts.mint;
+        require!(config.sz_mint == Some(mint.key()), EscrowError::MintMismatch);
+
+        // --- Burn ---
+        let cpi_accounts = Burn {
+            mint: mint.to_account_info(),
+            from: ctx.accounts.burner_token_account.to_account_info(),
+            authority: ctx.accounts.burner.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        burn(cpi_ctx, amount)?;
 
         // --- Update State ---
-        escrow_state.stage = EscrowStage::Released;
-        escrow_state.completed_at = Clock::get()?.unix_timestamp;
+        let config = &mut ctx.accounts.config;
+        config.total_burned = config.total_burned.checked_add(amount)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        let burn_target = 10u64.checked_pow(mint.decimals as u32)
+            .and_then(|unit| unit.checked_mul(BURN_TARGET_TOKENS))
+            .ok_or(EscrowError::ArithmeticOverflow)?;
 
-        emit!(EscrowCompleted {
-            escrow_id: escrow_state.key(),
-            buyer: escrow_state.buyer,
-            seller: escrow_state.seller,
-            amount: amount_to_transfer,
-            action: "released".to_string(),
-            timestamp: escrow_state.completed_at,
+        emit!(TokensBurned {
+            burner: ctx.accounts.burner.key(),
+            mint: mint.key(),
+            amount,
+            total_burned: config.total_burned,
+            burn_target,
+            timestamp: Clock::get()?.unix_timestamp,
         });
 
-        msg!("✅ Funds released to seller: {} - Amount: {}", escrow_state.seller, amount_to_transfer);
+        msg!("✅ Tokens burned - Amount: {}, Total burned: {} of {}", amount, config.total_burned, burn_target);
         Ok(())
     }
 
-    /// Cancels escrow and refunds buyer (minus fee) - only callable by marketplace authority
-    pub fn cancel_escrow_and_refund_buyer(ctx: Context<ProcessEscrow>, transaction_seed: u64) -> Result<()> {
-        let escrow_state = &mut ctx.accounts.escrow_state;
-        let caller = &ctx.accounts.caller;
+    /// Posts a holder airdrop epoch and funds it from the fee vault - only callable by marketplace authority.
+    /// Epochs can draw at most the airdrop share of the undistributed fee vault balance, together with
+    /// other epochs posted since the last `distribute_fees`.
+    /// Each claimant's amount `A = (P * H) / T` is computed off-chain; `merkle_root` commits to the
+    /// `(index, claimant, amount)` leaves. Unclaimed funds can be swept back after `claim_period`.
+    pub fn post_airdrop_epoch(
+        ctx: Context<PostAirdropEpoch>,
+        epoch: u64,
+        merkle_root: [u8; 32],
+        total_amount: u64,
+        max_claimants: u32,
+        claim_period: i64,
+    ) -> Result<()> {
+        require_keys_eq!(ctx.accounts.authority.key(), ctx.accounts.config.authority, EscrowError::Unauthorized);
+        ctx.accounts.config.check_not_paused(PAUSE_DISTRIBUTION)?;
+        require!(total_amount > 0, EscrowError::ZeroAmount);
+        require!(max_claimants > 0 && max_claimants <= MAX_AIRDROP_CLAIMANTS, EscrowError::InvalidAirdropParams);
+        require!(claim_period > 0, EscrowError::InvalidAirdropParams);
 
-        // --- Strict Authorization ---
-        let expected_authority = MARKETPLACE_AUTHORITY_PUBKEY_STR.parse::<Pubkey>()
-            .map_err(|_| EscrowError::InvalidAuthorityAddress)?;
-        require_keys_eq!(caller.key(), expected_authority, EscrowError::Unauthorized);
-        require_keys_eq!(caller.key(), escrow_state.marketplace_authority, EscrowError::Unauthorized);
+        // --- Fund Airdrop Vault ---
+        // The authority covers the vault's rent; the fee vault keeps its own rent-exempt minimum
+        let rent = Rent::get()?.minimum_balance(0);
+        let config = &ctx.accounts.config;
+        let available = ctx.accounts.fee_vault.lamports().saturating_sub(rent);
+        let undistributed = available.checked_add(config.airdrop_drawn)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        let airdrop_share = config.fee_distribution.split(undistributed)?[1];
+        let airdrop_drawn = config.airdrop_drawn.checked_add(total_amount)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        require!(airdrop_drawn <= airdrop_share, EscrowError::InsufficientFeeVaultBalance);
+        ctx.accounts.config.airdrop_drawn = airdrop_drawn;
 
-        // --- State Validation ---
-        require!(escrow_state.is_initialized, EscrowError::NotInitialized);
-        require!(escrow_state.stage == EscrowStage::Funded, EscrowError::AlreadyProcessedOrNotFunded);
-        require_keys_eq!(ctx.accounts.recipient_account.key(), escrow_state.buyer, EscrowError::RecipientNotBuyer);
+        let vault_rent = rent.saturating_sub(ctx.accounts.airdrop_vault.lamports());
+        if vault_rent > 0 {
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.authority.to_account_info(),
+                to: ctx.accounts.airdrop_vault.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(ctx.accounts.system_program.to_account_info(), cpi_accounts);
+            transfer(cpi_ctx, vault_rent)?;
+        }
 
-        let amount_to_refund = escrow_state.amount_for_seller; // Fee is NOT refunded
-        require!(amount_to_refund > 0, EscrowError::ZeroAmount); 
+        let fee_vault_seeds: &[&[u8]] = &[b"fee_vault".as_ref(), &[ctx.accounts.config.fee_vault_bump]];
+        let signer_seeds = &[fee_vault_seeds];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.fee_vault.to_account_info(),
+            to: ctx.accounts.airdrop_vault.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.system_program.to_account_info(), cpi_accounts, signer_seeds);
+        transfer(cpi_ctx, total_amount)?;
 
-        // --- Transfer to Buyer ---
+        // --- Initialize Epoch ---
+        let airdrop_epoch = &mut ctx.accounts.airdrop_epoch;
+        airdrop_epoch.epoch = epoch;
+        airdrop_epoch.merkle_root = merkle_root;
+        airdrop_epoch.total_amount = total_amount;
+        airdrop_epoch.claimed_amount = 0;
+        airdrop_epoch.max_claimants = max_claimants;
+        airdrop_epoch.created_at = Clock::get()?.unix_timestamp;
+        airdrop_epoch.expires_at = airdrop_epoch.created_at
+            .checked_add(claim_period)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        airdrop_epoch.claimed_bitmap = vec![0; AirdropEpoch::bitmap_len(max_claimants)];
+        airdrop_epoch.bump = ctx.bumps.airdrop_epoch;
+        airdrop_epoch.vault_bump = ctx.bumps.airdrop_vault;
+
+        emit!(AirdropEpochPosted {
+            epoch,
+            merkle_root,
+            total_amount,
+            max_claimants,
+            expires_at: airdrop_epoch.expires_at,
+            timestamp: airdrop_epoch.created_at,
+        });
+
+        msg!("✅ Airdrop epoch posted - Epoch: {}, Total: {}, Claimants: {}", epoch, total_amount, max_claimants);
+        Ok(())
+    }
+
+    /// Claims a holder's airdrop for an epoch with a Merkle proof of `(index, claimant, amount)`
+    pub fn claim_airdrop(
+        ctx: Context<ClaimAirdrop>,
+        epoch: u64,
+        index: u32,
+        amount: u64,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        let airdrop_epoch = &ctx.accounts.airdrop_epoch;
+        let claimant = ctx.accounts.claimant.key();
+
+        // --- Claim Validation ---
+        let claimed_at = Clock::get()?.unix_timestamp;
+        require!(claimed_at < airdrop_epoch.expires_at, EscrowError::AirdropExpired);
+        require!(index < airdrop_epoch.max_claimants, EscrowError::InvalidMerkleProof);
+        require!(!airdrop_epoch.is_claimed(index), EscrowError::AirdropAlreadyClaimed);
+        let leaf = hashv(&[&[0u8], &index.to_le_bytes(), claimant.as_ref(), &amount.to_le_bytes()]).to_bytes();
+        require!(verify_merkle_proof(&proof, airdrop_epoch.merkle_root, leaf), EscrowError::InvalidMerkleProof);
+
+        let claimed_amount = airdrop_epoch.claimed_amount.checked_add(amount)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        require!(claimed_amount <= airdrop_epoch.total_amount, EscrowError::AirdropExhausted);
+
+        // --- Transfer to Claimant ---
+        let airdrop_epoch_key = airdrop_epoch.key();
+        let vault_seeds: &[&[u8]] = &[b"airdrop_vault".as_ref(), airdrop_epoch_key.as_ref(), &[airdrop_epoch.vault_bump]];
+        let signer_seeds = &[vault_seeds];
         let cpi_accounts = Transfer {
-            from: escrow_state.to_account_info(),
-            to: ctx.accounts.recipient_account.to_account_info(),
+            from: ctx.accounts.airdrop_vault.to_account_info(),
+            to: ctx.accounts.claimant.to_account_info(),
         };
-        let bump_seed = escrow_state.bump;
-        let seeds = &[
-            b"escrow".as_ref(),
-            &transaction_seed.to_le_bytes(),
-            &[bump_seed],
-        ];
-        let signer_seeds = &[&seeds[..]];
-        let cpi_program = ctx.accounts.system_program.to_account_info();
-        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
-        transfer(cpi_ctx, amount_to_refund)?;
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.system_program.to_account_info(), cpi_accounts, signer_seeds);
+        transfer(cpi_ctx, amount)?;
 
         // --- Update State ---
-        escrow_state.stage = EscrowStage::Cancelled;
-        escrow_state.completed_at = Clock::get()?.unix_timestamp;
+        let airdrop_epoch = &mut ctx.accounts.airdrop_epoch;
+        airdrop_epoch.set_claimed(index);
+        airdrop_epoch.claimed_amount = claimed_amount;
 
-        emit!(EscrowCompleted {
-            escrow_id: escrow_state.key(),
-            buyer: escrow_state.buyer,
-            seller: escrow_state.seller,
-            amount: amount_to_refund,
-            action: "cancelled".to_string(),
-            timestamp: escrow_state.completed_at,
+        emit!(AirdropClaimed {
+            epoch,
+            index,
+            claimant,
+            amount,
+            timestamp: claimed_at,
         });
 
-        msg!("✅ Escrow cancelled, buyer refunded: {} - Amount: {} (Fee: {} kept)", 
-            escrow_state.buyer, amount_to_refund, escrow_state.fee_amount);
+        msg!("✅ Airdrop claimed - Epoch: {}, Claimant: {}, Amount: {}", epoch, claimant, amount);
         Ok(())
     }
-}
 
-// --- Account Structs ---
+    /// Returns an expired epoch's unclaimed funds to the fee vault and closes it - only callable by marketplace authority
+    pub fn sweep_airdrop(ctx: Context<SweepAirdrop>, epoch: u64) -> Result<()> {
+        require_keys_eq!(ctx.accounts.authority.key(), ctx.accounts.config.authority, EscrowError::Unauthorized);
+        let airdrop_epoch = &ctx.accounts.airdrop_epoch;
+        let swept_at = Clock::get()?.unix_timestamp;
+        require!(swept_at >= airdrop_epoch.expires_at, EscrowError::AirdropNotExpired);
 
-#[derive(Accounts)]
-#[instruction(transaction_seed: u64)]
-pub struct InitializeEscrow<'info> {
-    #[account(mut)]
-    pub buyer: Signer<'info>,
+        // --- Return Unclaimed Funds ---
+        // The vault's rent goes back too; it came from the authority but the vault closes with the epoch
+        let amount = ctx.accounts.airdrop_vault.lamports();
+        if amount > 0 {
+            let airdrop_epoch_key = airdrop_epoch.key();
+            let vault_seeds: &[&[u8]] = &[b"airdrop_vault".as_ref(), airdrop_epoch_key.as_ref(), &[airdrop_epoch.vault_bump]];
+            let signer_seeds = &[vault_seeds];
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.airdrop_vault.to_account_info(),
+                to: ctx.accounts.fee_vault.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.system_program.to_account_info(), cpi_accounts, signer_seeds);
+            transfer(cpi_ctx, amount)?;
+        }
 
-    /// CHECK: Seller's account - validated in instruction
-    pub seller: AccountInfo<'info>,
+        // Epoch account itself is closed to the authority by the `close` constraint
+        emit!(AirdropSwept {
+            epoch,
+            total_amount: airdrop_epoch.total_amount,
+            claimed_amount: airdrop_epoch.claimed_amount,
+            swept_amount: amount,
+            timestamp: swept_at,
+        });
 
-    /// CHECK: Marketplace authority - validated against hardcoded address
-    pub marketplace_authority: AccountInfo<'info>,
+        msg!("✅ Airdrop swept - Epoch: {}, Claimed: {}, Swept: {}", epoch, airdrop_epoch.claimed_amount, amount);
+        Ok(())
+    }
 
-    #[account(
-        init,
-        payer = buyer,
-        space = EscrowState::LEN,
-        seeds = [b"escrow".as_ref(), transaction_seed.to_le_bytes().as_ref()],
-        bump
-    )]
-    pub escrow_state: Account<'info, EscrowState>,
+    /// Pauses the instruction groups in `paused_instructions` (a mask of `PAUSE_*` flags, 0 to resume) -
+    /// only callable by marketplace authority. Refunds stay available while paused.
+    pub fn set_paused(ctx: Context<UpdateConfig>, paused_instructions: u16) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        require_keys_eq!(ctx.accounts.authority.key(), config.authority, EscrowError::Unauthorized);
+        require!(paused_instructions & !PAUSE_ALL == 0, EscrowError::InvalidPauseFlags);
 
-    /// CHECK: Marketplace fee wallet - validated against hardcoded address
-    #[account(mut)]
-    pub marketplace_fee_wallet: AccountInfo<'info>,
+        config.paused_instructions = paused_instructions;
 
-    pub system_program: Program<'info, System>,
-}
+        emit!(PauseUpdated {
+            paused_instructions,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
 
-#[derive(Accounts)]
-#[instruction(transaction_seed: u64)]
-pub struct ProcessEscrow<'info> {
-    #[account(mut)]
-    pub caller: Signer<'info>,
+        msg!("✅ Pause flags updated - Paused: {:#06b}", paused_instructions);
+        Ok(())
+    }
 
-    #[account(
-        mut,
-        seeds = [b"escrow".as_ref(), transaction_seed.to_le_bytes().as_ref()],
-        bump = escrow_state.bump,
-    )]
-    pub escrow_state: Account<'info, EscrowState>,
-    
-    /// CHECK: Recipient account - validated in instruction logic
-    #[account(mut)]
-    pub recipient_account: AccountInfo<'info>,
+    /// Sets which Token-2022 mint extensions token escrows accept - only callable by marketplace authority
+    pub fn set_token_policy(ctx: Context<UpdateConfig>, reject_permanent_delegate: bool) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        require_keys_eq!(ctx.accounts.authority.key(), config.authority, EscrowError::Unauthorized);
 
-    pub system_program: Program<'info, System>,
-}
+        config.reject_permanent_delegate = reject_permanent_delegate;
 
-// --- State Account ---
+        emit!(TokenPolicyUpdated {
+            reject_permanent_delegate,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
 
-#[account]
-pub struct EscrowState {
-    pub buyer: Pubkey,                  // 32 bytes
-    pub seller: Pubkey,                 // 32 bytes  
-    pub marketplace_authority: Pubkey,  // 32 bytes
-    pub total_initial_amount: u64,      // 8 bytes
-    pub fee_amount: u64,                // 8 bytes
-    pub amount_for_seller: u64,         // 8 bytes
-    pub stage: EscrowStage,             // 1 byte
-    pub is_initialized: bool,           // 1 byte
-    pub bump: u8,                       // 1 byte
-    pub created_at: i64,                // 8 bytes - timestamp
-    pub completed_at: i64,              // 8 bytes - completion timestamp
-}
+        msg!("✅ Token policy updated - Reject permanent delegate: {}", reject_permanent_delegate);
+        Ok(())
+    }
 
-impl EscrowState {
-    // 8 (discriminator) + 32*3 (pubkeys) + 8*5 (u64s) + 1 (enum) + 1 (bool) + 1 (u8) + 8*2 (timestamps)
-    const LEN: usize = 8 + (32 * 3) + (8 * 5) + 1 + 1 + 1 + (8 * 2);
-}
+    /// Sets the delivery and confirmation windows applied to new escrows - only callable by marketplace authority
+    pub fn set_deadline_periods(
+        ctx: Context<UpdateConfig>,
+        delivery_period: i64,
+        confirmation_period: i64,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        require_keys_eq!(ctx.accounts.authority.key(), config.authority, EscrowError::Unauthorized);
+        require!(delivery_period > 0 && confirmation_period > 0, EscrowError::InvalidDeadlinePeriod);
 
-// --- Events ---
+        config.delivery_period = delivery_period;
+        config.confirmation_period = confirmation_period;
 
-#[event]
-pub struct EscrowCreated {
-    pub escrow_id: Pubkey,
-    pub buyer: Pubkey,
-    pub seller: Pubkey,
-    pub amount: u64,
-    pub fee: u64,
-    pub timestamp: i64,
-}
+        emit!(DeadlinePeriodsUpdated {
+            delivery_period,
+            confirmation_period,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
 
-#[event]
-pub struct EscrowCompleted {
-    pub escrow_id: Pubkey,
-    pub buyer: Pubkey,
-    pub seller: Pubkey,
-    pub amount: u64,
-    pub action: String,
-    pub timestamp: i64,
-}
+        msg!("✅ Deadline periods updated - Delivery: {}s, Confirmation: {}s", delivery_period, confirmation_period);
+        Ok(())
+    }
 
-// --- Enums ---
+    /// Sets who resolves disputes - only callable by marketplace authority
+    pub fn set_arbiter(ctx: Context<UpdateConfig>, arbiter: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        require_keys_eq!(ctx.accounts.authority.key(), config.authority, EscrowError::Unauthorized);
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Copy, Debug)]
-pub enum EscrowStage {
-    Funded,     // Escrow active, funds held
-    Released,   // Funds released to seller
-    Cancelled,  // Funds returned to buyer (minus fee)
+        let previous_arbiter = config.arbiter;
+        config.arbiter = arbiter;
+
+        emit!(ArbiterUpdated {
+            previous_arbiter,
+            arbiter,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("✅ Arbiter updated - Previous: {}, New: {}", previous_arbiter, arbiter);
+        Ok(())
+    }
+
+    /// Starts an authority transfer - only callable by marketplace authority.
+    /// The new authority has no power until it signs `accept_authority`.
+    pub fn propose_authority(ctx: Context<UpdateConfig>, new_authority: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        require_keys_eq!(ctx.accounts.authority.key(), config.authority, EscrowError::Unauthorized);
+        require_keys_neq!(new_authority, config.authority, EscrowError::AuthorityUnchanged);
+
+        config.pending_authority = Some(new_authority);
+
+        emit!(AuthorityProposed {
+            authority: config.authority,
+            pending_authority: new_authority,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("✅ Authority transfer proposed - Current: {}, Pending: {}", config.authority, new_authority);
+        Ok(())
+    }
+
+    /// Completes an authority transfer - only callable by the pending authority.
+    /// An arbiter that was still the outgoing authority moves to the new authority with it.
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let pending_authority = config.pending_authority.ok_or(EscrowError::NoPendingAuthority)?;
+        require_keys_eq!(ctx.accounts.new_authority.key(), pending_authority, EscrowError::NotPendingAuthority);
+
+        let previous_authority = config.authority;
+        config.authority = pending_authority;
+        config.pending_authority = None;
+
+        let now = Clock::get()?.unix_timestamp;
+        // Otherwise the retired key could keep settling disputed funds
+        if config.arbiter == previous_authority {
+            config.arbiter = pending_authority;
+            emit!(ArbiterUpdated {
+                previous_arbiter: previous_authority,
+                arbiter: pending_authority,
+                timestamp: now,
+            });
+        }
+
+        emit!(AuthorityAccepted {
+            previous_authority,
+            authority: config.authority,
+            timestamp: now,
+        });
+
+        msg!("✅ Authority transferred - Previous: {}, New: {}", previous_authority, config.authority);
+        Ok(())
+    }
+
+    /// Withdraws a pending authority transfer - only callable by marketplace authority
+    pub fn cancel_authority_proposal(ctx: Context<UpdateConfig>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        require_keys_eq!(ctx.accounts.authority.key(), config.authority, EscrowError::Unauthorized);
+        let pending_authority = config.pending_authority.ok_or(EscrowError::NoPendingAuthority)?;
+
+        config.pending_authority = None;
+
+        emit!(AuthorityProposalCancelled {
+            authority: config.authority,
+            cancelled_authority: pending_authority,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("✅ Authority transfer cancelled - Withdrawn: {}", pending_authority);
+        Ok(())
+    }
+
+    /// Initializes a new escrow, awaiting the seller's `accept_order`.
+    /// The buyer deposits the escrow amount plus the buyer fee on top, charged when the seller
+    /// accepts; the seller fee is deducted from the held amount at release. Both rates are read from the config and
+    /// snapshotted on the escrow; clients have no way to supply their own rate.
+    /// The fee schedule's amount tier and the seller's fee override, whenever they exist, can lower the rates;
+    /// buyers passing a qualifying $SZ token account get the holder discount on the buyer fee.
+    /// `order_reference` (e.g. a hash of the listing id and order metadata) ties the escrow to the order.
+    /// Only the configured marketplace authority can later release or cancel.
+    pub fn initialize_escrow(
+        ctx: Context<InitializeEscrow>,
+        transaction_seed: u64, 
+        total_amount_to_escrow: u64, 
+        order_reference: [u8; 32],
+    ) -> Result<()> {
+        ctx.accounts.fund(&ctx.bumps, transaction_seed, total_amount_to_escrow, order_reference, EscrowStage::Funded)
+    }
+
+    /// Initializes a new SPL token escrow (e.g. USDC or $SZ).
+    /// Same fee logic and holder discount as `initialize_escrow`, but funds sit in an associated token account
+    /// owned by the escrow PDA and fees go to the fee vault's token account once charged.
+    /// Works with Token-2022 mints: the amount held for the seller is what the vault actually
+    /// received after any mint-level transfer fee.
+    pub fn initialize_token_escrow(
+        ctx: Context<InitializeTokenEscrow>,
+        transaction_seed: u64, 
+        total_amount_to_escrow: u64, 
+        order_reference: [u8; 32],
+    ) -> Result<()> {
+        ctx.accounts.fund(&ctx.bumps, transaction_seed, total_amount_to_escrow, order_reference)
+    }
+
+    /// Creates a fixed-price listing - only callable by the seller, who pays its rent.
+    /// `mint` is the listing currency: None for SOL, otherwise the SPL mint buyers pay in.
+    /// `price` is per unit, in lamports or token base units.
+    pub fn create_listing(
+        ctx: Context<CreateListing>,
+        listing_id: u64,
+        price: u64,
+        mint: Option<Pubkey>,
+        quantity: u32,
+    ) -> Result<()> {
+        // --- Enhanced Validation ---
+        require!(price > 0 && quantity > 0, EscrowError::ZeroAmount);
+        // Minimum amounts in config are denominated in lamports, so they only apply to SOL listings
+        if mint.is_none() {
+            require!(price >= ctx.accounts.config.min_escrow_amount, EscrowError::MinimumAmount);
+        }
+
+        let listing = &mut ctx.accounts.listing;
+        listing.seller = ctx.accounts.seller.key();
+        listing.listing_id = listing_id;
+        listing.price = price;
+        listing.mint = mint;
+        listing.quantity = quantity;
+        listing.status = ListingStatus::Active;
+        listing.created_at = Clock::get()?.unix_timestamp;
+        listing.bump = ctx.bumps.listing;
+
+        emit!(ListingCreated {
+            listing: listing.key(),
+            seller: listing.seller,
+            listing_id,
+            mint,
+            price,
+            quantity,
+            timestamp: listing.created_at,
+        });
+
+        msg!("✅ Listing created - Seller: {}, Id: {}, Price: {}, Quantity: {}",
+            listing.seller, listing_id, price, quantity);
+        Ok(())
+    }
+
+    /// Changes a listing's unit price - only callable by the seller.
+    /// Escrows already opened against the listing keep the price they were funded at.
+    pub fn update_listing(ctx: Context<ManageListing>, price: u64) -> Result<()> {
+        let listing = &mut ctx.accounts.listing;
+        require_keys_eq!(ctx.accounts.seller.key(), listing.seller, EscrowError::NotSeller);
+
+        require!(price > 0, EscrowError::ZeroAmount);
+        if listing.mint.is_none() {
+            require!(price >= ctx.accounts.config.min_escrow_amount, EscrowError::MinimumAmount);
+        }
+        listing.price = price;
+
+        emit_listing_updated(listing)?;
+        msg!("✅ Listing price updated - Listing: {}, Price: {}", listing.key(), price);
+        Ok(())
+    }
+
+    /// Pauses or resumes purchases of a listing - only callable by the seller
+    pub fn pause_listing(ctx: Context<ManageListing>, paused: bool) -> Result<()> {
+        let listing = &mut ctx.accounts.listing;
+        require_keys_eq!(ctx.accounts.seller.key(), listing.seller, EscrowError::NotSeller);
+
+        listing.status = if paused {
+            ListingStatus::Paused
+        } else if listing.quantity == 0 {
+            ListingStatus::SoldOut
+        } else {
+            ListingStatus::Active
+        };
+
+        emit_listing_updated(listing)?;
+        msg!("✅ Listing {} - Listing: {}", if paused { "paused" } else { "resumed" }, listing.key());
+        Ok(())
+    }
+
+    /// Adds `quantity` units to a listing's inventory - only callable by the seller.
+    /// A sold-out listing becomes purchasable again; a paused one stays paused.
+    pub fn restock_listing(ctx: Context<ManageListing>, quantity: u32) -> Result<()> {
+        let listing = &mut ctx.accounts.listing;
+        require_keys_eq!(ctx.accounts.seller.key(), listing.seller, EscrowError::NotSeller);
+
+        require!(quantity > 0, EscrowError::ZeroAmount);
+        listing.quantity = listing.quantity
+            .checked_add(quantity)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        if listing.status == ListingStatus::SoldOut {
+            listing.status = ListingStatus::Active;
+        }
+
+        emit_listing_updated(listing)?;
+        msg!("✅ Listing restocked - Listing: {}, Quantity: {}", listing.key(), listing.quantity);
+        Ok(())
+    }
+
+    /// Delists a listing and returns its rent to the seller - only callable by the seller.
+    /// Escrows already opened against the listing are unaffected.
+    pub fn close_listing(ctx: Context<CloseListing>) -> Result<()> {
+        let listing = &ctx.accounts.listing;
+        require_keys_eq!(ctx.accounts.seller.key(), listing.seller, EscrowError::NotSeller);
+
+        emit!(ListingClosed {
+            listing: listing.key(),
+            seller: listing.seller,
+            listing_id: listing.listing_id,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("✅ Listing closed - Seller: {}, Id: {}", listing.seller, listing.listing_id);
+        Ok(())
+    }
+
+    /// Buys `quantity` units of a SOL listing: opens an escrow for exactly the listing price
+    /// times `quantity` and takes the units out of the listing's inventory in the same transaction.
+    /// Fees and deadlines work as in `initialize_escrow`.
+    pub fn purchase_listing(
+        ctx: Context<PurchaseListing>,
+        transaction_seed: u64,
+        quantity: u32,
+        order_reference: [u8; 32],
+    ) -> Result<()> {
+        let total_price = ctx.accounts.listing.reserve(ctx.accounts.escrow.seller.key(), None, quantity)?;
+        ctx.accounts.escrow.fund(&ctx.bumps.escrow, transaction_seed, total_price, order_reference, EscrowStage::Funded)?;
+        let escrow_state = &mut ctx.accounts.escrow.escrow_state;
+        escrow_state.listing = Some(ctx.accounts.listing.key());
+        escrow_state.listing_quantity = quantity;
+
+        let listing = &ctx.accounts.listing;
+        emit!(ListingPurchased {
+            listing: listing.key(),
+            escrow_id: ctx.accounts.escrow.escrow_state.key(),
+            buyer: ctx.accounts.escrow.buyer.key(),
+            quantity,
+            total_price,
+            remaining_quantity: listing.quantity,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("✅ Listing purchased - Listing: {}, Quantity: {}, Remaining: {}, Seed: {}",
+            listing.key(), quantity, listing.quantity, transaction_seed);
+        Ok(())
+    }
+
+    /// Token counterpart of `purchase_listing` for listings priced in an SPL mint
+    pub fn purchase_token_listing(
+        ctx: Context<PurchaseTokenListing>,
+        transaction_seed: u64,
+        quantity: u32,
+        order_reference: [u8; 32],
+    ) -> Result<()> {
+        let total_price = ctx.accounts.listing.reserve(
+            ctx.accounts.escrow.seller.key(),
+            Some(ctx.accounts.escrow.mint.key()),
+            quantity,
+        )?;
+        ctx.accounts.escrow.fund(&ctx.bumps.escrow, transaction_seed, total_price, order_reference)?;
+        let escrow_state = &mut ctx.accounts.escrow.escrow_state;
+        escrow_state.listing = Some(ctx.accounts.listing.key());
+        escrow_state.listing_quantity = quantity;
+
+        let listing = &ctx.accounts.listing;
+        emit!(ListingPurchased {
+            listing: listing.key(),
+            escrow_id: ctx.accounts.escrow.escrow_state.key(),
+            buyer: ctx.accounts.escrow.buyer.key(),
+            quantity,
+            total_price,
+            remaining_quantity: listing.quantity,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("✅ Token listing purchased - Listing: {}, Quantity: {}, Remaining: {}, Seed: {}",
+            listing.key(), quantity, listing.quantity, transaction_seed);
+        Ok(())
+    }
+
+    /// Makes an offer: the buyer deposits the offered amount plus the buyer fee into a SOL escrow
+    /// in the `Offered` stage. Fee rates are fixed here as in `initialize_escrow`, but nothing is
+    /// charged until the seller accepts; a rejected or withdrawn offer is refunded in full.
+    pub fn make_offer(
+        ctx: Context<InitializeEscrow>,
+        transaction_seed: u64,
+        offer_amount: u64,
+        order_reference: [u8; 32],
+    ) -> Result<()> {
+        ctx.accounts.fund(&ctx.bumps, transaction_seed, offer_amount, order_reference, EscrowStage::Offered)
+    }
+
+    /// Accepts the buyer's offer - only callable by the seller.
+    /// Charges the held buyer fee and starts the delivery deadline; any pending counter-offer lapses.
+    pub fn accept_offer(ctx: Context<RespondToOffer>, transaction_seed: u64) -> Result<()> {
+        require_keys_eq!(ctx.accounts.caller.key(), ctx.accounts.escrow_state.seller, EscrowError::NotSeller);
+
+        ctx.accounts.accept()?;
+
+        msg!("✅ Offer accepted - Seller: {}, Amount: {}, Seed: {}",
+            ctx.accounts.escrow_state.seller, ctx.accounts.escrow_state.amount_for_seller, transaction_seed);
+        Ok(())
+    }
+
+    /// Rejects the buyer's offer - only callable by the seller.
+    /// Refunds the offered amount and the held buyer fee; no fee is charged.
+    pub fn reject_offer(ctx: Context<RespondToOffer>, transaction_seed: u64) -> Result<()> {
+        require_keys_eq!(ctx.accounts.caller.key(), ctx.accounts.escrow_state.seller, EscrowError::NotSeller);
+
+        let amount_refunded = ctx.accounts.refund_offer(CompletionAction::OfferRejected)?;
+
+        msg!("✅ Offer rejected - Buyer: {}, Refunded: {}, Seed: {}",
+            ctx.accounts.escrow_state.buyer, amount_refunded, transaction_seed);
+        Ok(())
+    }
+
+    /// Proposes a different price for an open offer - only callable by the seller.
+    /// The buyer settles the difference when accepting it; a new counter replaces the previous one.
+    pub fn counter_offer(ctx: Context<RespondToOffer>, transaction_seed: u64, counter_amount: u64) -> Result<()> {
+        let config = &ctx.accounts.config;
+        let escrow_state = &mut ctx.accounts.escrow_state;
+        require_keys_eq!(ctx.accounts.caller.key(), escrow_state.seller, EscrowError::NotSeller);
+
+        // --- State Validation ---
+        require!(escrow_state.stage == EscrowStage::Offered, EscrowError::NotOffered);
+        require!(counter_amount > 0, EscrowError::ZeroAmount);
+        require!(counter_amount >= config.min_escrow_amount, EscrowError::MinimumAmount);
+
+        escrow_state.counter_amount = counter_amount;
+
+        emit!(OfferCountered {
+            escrow_id: escrow_state.key(),
+            buyer: escrow_state.buyer,
+            seller: escrow_state.seller,
+            offer_amount: escrow_state.amount_for_seller,
+            counter_amount,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("✅ Offer countered - Offer: {}, Counter: {}, Seed: {}",
+            escrow_state.amount_for_seller, counter_amount, transaction_seed);
+        Ok(())
+    }
+
+    /// Accepts the seller's counter-offer - only callable by the buyer.
+    /// Fee rates are resolved again for the counter amount, so its tier is the one that applies; the
+    /// buyer tops up the vault or gets the excess back, then the escrow is accepted as in `accept_offer`.
+    pub fn accept_counter_offer(ctx: Context<RespondToOffer>, transaction_seed: u64) -> Result<()> {
+        let escrow_state = &ctx.accounts.escrow_state;
+        require_keys_eq!(ctx.accounts.caller.key(), escrow_state.buyer, EscrowError::NotBuyer);
+        require_keys_eq!(ctx.accounts.buyer.key(), escrow_state.buyer, EscrowError::RecipientNotBuyer);
+
+        // --- State Validation ---
+        require!(escrow_state.stage == EscrowStage::Offered, EscrowError::NotOffered);
+        let counter_amount = escrow_state.counter_amount;
+        require!(counter_amount > 0, EscrowError::NoCounterOffer);
+
+        // --- Fee Calculation with Safety ---
+        let fee_schedule = load_if_initialized::<FeeSchedule>(&ctx.accounts.fee_schedule)?;
+        let seller_fee_override = load_if_initialized::<SellerFeeOverride>(&ctx.accounts.seller_fee_override)?;
+        let fee_rates = ctx.accounts.config.fee_rates(
+            Some(counter_amount),
+            fee_schedule.as_ref(),
+            seller_fee_override.as_ref(),
+            escrow_state.buyer,
+            ctx.accounts.holder_token_account.as_ref(),
+        );
+        let buyer_fee_amount = calculate_fee(counter_amount, fee_rates.buyer_fee_basis_points)?;
+        let seller_fee_amount = calculate_fee(counter_amount, fee_rates.seller_fee_basis_points)?;
+        require!(counter_amount > seller_fee_amount, EscrowError::AmountLessThanFee);
+        let seller_net_amount = counter_amount.checked_sub(seller_fee_amount)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        require!(seller_net_amount >= ctx.accounts.config.min_net_amount, EscrowError::NetAmountTooSmall);
+
+        // --- Settle the Difference ---
+        let held = escrow_state.amount_for_seller.checked_add(escrow_state.buyer_fee_amount)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        let owed = counter_amount.checked_add(buyer_fee_amount)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        if owed > held {
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.caller.to_account_info(),
+                to: ctx.accounts.vault.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(ctx.accounts.system_program.to_account_info(), cpi_accounts);
+            transfer(cpi_ctx, owed - held)?;
+        } else if held > owed {
+            transfer_from_vault(
+                escrow_state,
+                &ctx.accounts.vault,
+                &ctx.accounts.buyer.to_account_info(),
+                &ctx.accounts.system_program,
+                held - owed,
+            )?;
+        }
+
+        let escrow_state = &mut ctx.accounts.escrow_state;
+        escrow_state.total_initial_amount = counter_amount;
+        escrow_state.amount_for_seller = counter_amount;
+        escrow_state.buyer_fee_amount = buyer_fee_amount;
+        escrow_state.seller_fee_amount = seller_fee_amount;
+        escrow_state.buyer_fee_basis_points = fee_rates.buyer_fee_basis_points;
+        escrow_state.seller_fee_basis_points = fee_rates.seller_fee_basis_points;
+        escrow_state.fee_discount_applied = fee_rates.fee_discount_applied;
+        escrow_state.fee_tier = fee_rates.fee_tier;
+        escrow_state.fee_override_applied = fee_rates.fee_override_applied;
+
+        ctx.accounts.accept()?;
+
+        msg!("✅ Counter-offer accepted - Buyer: {}, Amount: {}, Seed: {}",
+            ctx.accounts.escrow_state.buyer, counter_amount, transaction_seed);
+        Ok(())
+    }
+
+    /// Withdraws an offer the seller has not accepted - only callable by the buyer.
+    /// Refunds the offered amount and the held buyer fee; no fee is charged.
+    pub fn withdraw_offer(ctx: Context<RespondToOffer>, transaction_seed: u64) -> Result<()> {
+        require_keys_eq!(ctx.accounts.caller.key(), ctx.accounts.escrow_state.buyer, EscrowError::NotBuyer);
+
+        let amount_refunded = ctx.accounts.refund_offer(CompletionAction::OfferWithdrawn)?;
+
+        msg!("✅ Offer withdrawn - Buyer: {}, Refunded: {}, Seed: {}",
+            ctx.accounts.escrow_state.buyer, amount_refunded, transaction_seed);
+        Ok(())
+    }
+
+    /// Accepts a funded order - only callable by the seller.
+    /// Charges the held buyer fee and starts the delivery and confirmation deadlines.
+    pub fn accept_order(ctx: Context<ProcessEscrow>, transaction_seed: u64) -> Result<()> {
+        let escrow_state = &ctx.accounts.escrow_state;
+        require_keys_eq!(ctx.accounts.caller.key(), escrow_state.seller, EscrowError::NotSeller);
+
+        // --- State Validation ---
+        require!(escrow_state.is_initialized, EscrowError::NotInitialized);
+        require!(escrow_state.stage == EscrowStage::Funded, EscrowError::AlreadyProcessedOrNotFunded);
+
+        let buyer_fee_amount = escrow_state.buyer_fee_amount;
+        if buyer_fee_amount > 0 {
+            ctx.accounts.collect_fee(transaction_seed, buyer_fee_amount)?;
+        }
+
+        let now = Clock::get()?.unix_timestamp;
+        let escrow_state = &mut ctx.accounts.escrow_state;
+        escrow_state.stage = EscrowStage::Accepted;
+        escrow_state.set_deadlines(&ctx.accounts.config, now)?;
+
+        emit!(OrderAccepted {
+            escrow_id: escrow_state.key(),
+            buyer: escrow_state.buyer,
+            seller: escrow_state.seller,
+            buyer_fee: buyer_fee_amount,
+            delivery_deadline: escrow_state.delivery_deadline,
+            timestamp: now,
+        });
+
+        msg!("✅ Order accepted - Seller: {}, Delivery deadline: {}, Seed: {}",
+            escrow_state.seller, escrow_state.delivery_deadline, transaction_seed);
+        Ok(())
+    }
+
+    /// Cancels an order the seller has not accepted yet - only callable by the buyer.
+    /// Refunds the held amount and the buyer fee in full and restocks the listing it was bought from.
+    pub fn cancel_order(ctx: Context<ProcessEscrow>, transaction_seed: u64) -> Result<()> {
+        require_keys_eq!(ctx.accounts.caller.key(), ctx.accounts.escrow_state.buyer, EscrowError::NotBuyer);
+
+        let amount_to_refund = ctx.accounts.refund_to_buyer(transaction_seed, CompletionAction::BuyerCancelled)?;
+
+        msg!("✅ Order cancelled by buyer: {} - Amount: {}, Seed: {}",
+            ctx.accounts.escrow_state.buyer, amount_to_refund, transaction_seed);
+        Ok(())
+    }
+
+    /// Declines an order the seller has not accepted yet (e.g. out of stock) - only callable by the seller.
+    /// Refunds the buyer exactly as `cancel_order` does, including restocking the listing.
+    pub fn reject_order(ctx: Context<ProcessEscrow>, transaction_seed: u64) -> Result<()> {
+        require_keys_eq!(ctx.accounts.caller.key(), ctx.accounts.escrow_state.seller, EscrowError::NotSeller);
+
+        let amount_to_refund = ctx.accounts.refund_to_buyer(transaction_seed, CompletionAction::SellerRejected)?;
+
+        msg!("✅ Order rejected by seller: {} - Amount: {}, Seed: {}",
+            ctx.accounts.escrow_state.seller, amount_to_refund, transaction_seed);
+        Ok(())
+    }
+
+    /// Releases funds to seller - only callable by marketplace authority
+    pub fn release_funds_to_seller(ctx: Context<ProcessEscrow>, transaction_seed: u64) -> Result<()> {
+        // --- Strict Authorization ---
+        require_keys_eq!(ctx.accounts.caller.key(), ctx.accounts.config.authority, EscrowError::Unauthorized);
+
+        let amount_to_transfer = ctx.accounts.release_to_seller(transaction_seed, CompletionAction::Released)?;
+
+        msg!("✅ Funds released to seller: {} - Amount: {}, Seed: {}", 
+            ctx.accounts.escrow_state.seller, amount_to_transfer, transaction_seed);
+        Ok(())
+    }
+
+    /// Releases funds to seller once the buyer confirms receipt - only callable by the buyer.
+    /// Lets happy-path orders settle without the marketplace authority being online.
+    pub fn confirm_receipt(ctx: Context<ProcessEscrow>, transaction_seed: u64) -> Result<()> {
+        // --- Strict Authorization ---
+        require_keys_eq!(ctx.accounts.caller.key(), ctx.accounts.escrow_state.buyer, EscrowError::NotBuyer);
+
+        let amount_to_transfer = ctx.accounts.release_to_seller(transaction_seed, CompletionAction::BuyerConfirmed)?;
+
+        msg!("✅ Buyer confirmed receipt, funds released to seller: {} - Amount: {}, Seed: {}", 
+            ctx.accounts.escrow_state.seller, amount_to_transfer, transaction_seed);
+        Ok(())
+    }
+
+    /// Records that the order has shipped - only callable by the seller.
+    /// Stores a commitment to the tracking number (e.g. a hash of it with a salt) and the carrier code.
+    /// Shipping before the delivery deadline stops the escrow from being auto-refunded, and the
+    /// buyer's confirmation window runs from the shipped time.
+    pub fn mark_shipped(
+        ctx: Context<MarkShipped>,
+        transaction_seed: u64,
+        tracking_hash: [u8; 32],
+        carrier_code: u16,
+    ) -> Result<()> {
+        let escrow_state = &mut ctx.accounts.escrow_state;
+        require_keys_eq!(ctx.accounts.seller.key(), escrow_state.seller, EscrowError::NotSeller);
+
+        // --- State Validation ---
+        require!(escrow_state.stage != EscrowStage::Funded, EscrowError::NotAccepted);
+        require!(escrow_state.stage.is_active(), EscrowError::AlreadyProcessedOrNotFunded);
+        require!(escrow_state.shipped_at == 0, EscrowError::AlreadyShipped);
+        let now = Clock::get()?.unix_timestamp;
+        require!(now <= escrow_state.delivery_deadline, EscrowError::DeliveryDeadlinePassed);
+
+        escrow_state.stage = EscrowStage::Shipped;
+        escrow_state.shipped_at = now;
+        escrow_state.tracking_hash = tracking_hash;
+        escrow_state.carrier_code = carrier_code;
+        escrow_state.confirmation_deadline = now
+            .checked_add(ctx.accounts.config.confirmation_period)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+
+        emit!(OrderShipped {
+            escrow_id: escrow_state.key(),
+            buyer: escrow_state.buyer,
+            seller: escrow_state.seller,
+            tracking_hash,
+            carrier_code,
+            confirmation_deadline: escrow_state.confirmation_deadline,
+            timestamp: now,
+        });
+
+        msg!("✅ Order shipped - Seller: {}, Carrier: {}, Seed: {}", escrow_state.seller, carrier_code, transaction_seed);
+        Ok(())
+    }
+
+    /// Releases funds to seller after the buyer's confirmation window lapses - callable by anyone
+    pub fn auto_release_to_seller(ctx: Context<ProcessEscrow>, transaction_seed: u64) -> Result<()> {
+        let escrow_state = &ctx.accounts.escrow_state;
+        require!(escrow_state.shipped_at > 0, EscrowError::NotShipped);
+        require!(
+            Clock::get()?.unix_timestamp > escrow_state.confirmation_deadline,
+            EscrowError::DeadlineNotReached
+        );
+
+        let amount_to_transfer = ctx.accounts.release_to_seller(transaction_seed, CompletionAction::AutoReleased)?;
+
+        msg!("✅ Confirmation window lapsed, funds released to seller: {} - Amount: {}, Seed: {}", 
+            ctx.accounts.escrow_state.seller, amount_to_transfer, transaction_seed);
+        Ok(())
+    }
+
+    /// Refunds buyer (minus fee) when the seller never shipped by the delivery deadline - callable by anyone
+    pub fn auto_refund_buyer(ctx: Context<ProcessEscrow>, transaction_seed: u64) -> Result<()> {
+        let escrow_state = &ctx.accounts.escrow_state;
+        require!(escrow_state.shipped_at == 0, EscrowError::AlreadyShipped);
+        require!(
+            Clock::get()?.unix_timestamp > escrow_state.delivery_deadline,
+            EscrowError::DeadlineNotReached
+        );
+
+        let amount_to_refund = ctx.accounts.refund_to_buyer(transaction_seed, CompletionAction::AutoRefunded)?;
+
+        msg!("✅ Delivery deadline missed, buyer refunded: {} - Amount: {}, Seed: {}", 
+            ctx.accounts.escrow_state.buyer, amount_to_refund, transaction_seed);
+        Ok(())
+    }
+
+    /// Opens a dispute on an accepted escrow - callable by the buyer or the seller.
+    /// Freezes every settlement path except the arbiter's `resolve_dispute`.
+    pub fn open_dispute(ctx: Context<OpenDispute>, transaction_seed: u64) -> Result<()> {
+        let escrow_state = &mut ctx.accounts.escrow_state;
+        let party = ctx.accounts.party.key();
+        require!(party == escrow_state.buyer || party == escrow_state.seller, EscrowError::NotEscrowParty);
+
+        // --- State Validation ---
+        require!(escrow_state.stage.is_active(), EscrowError::AlreadyProcessedOrNotFunded);
+
+        let now = Clock::get()?.unix_timestamp;
+        escrow_state.stage = EscrowStage::Disputed;
+
+        let dispute = &mut ctx.accounts.dispute;
+        dispute.escrow = escrow_state.key();
+        dispute.opened_by = party;
+        dispute.opened_at = now;
+        dispute.evidence = Vec::new();
+        dispute.bump = ctx.bumps.dispute;
+
+        emit!(DisputeOpened {
+            escrow_id: escrow_state.key(),
+            opened_by: party,
+            timestamp: now,
+        });
+
+        msg!("⚠️ Dispute opened - Escrow: {}, By: {}, Seed: {}", escrow_state.key(), party, transaction_seed);
+        Ok(())
+    }
+
+    /// Adds an evidence content hash (e.g. a SHA-256 or IPFS digest) to an open dispute -
+    /// callable by the buyer or the seller, each up to their own evidence limit
+    pub fn submit_evidence(ctx: Context<SubmitEvidence>, transaction_seed: u64, content_hash: [u8; 32]) -> Result<()> {
+        let escrow_state = &ctx.accounts.escrow_state;
+        let party = ctx.accounts.party.key();
+        require!(party == escrow_state.buyer || party == escrow_state.seller, EscrowError::NotEscrowParty);
+        require!(escrow_state.stage == EscrowStage::Disputed, EscrowError::NotDisputed);
+
+        let dispute = &mut ctx.accounts.dispute;
+        // Each side has its own slots so one party can't crowd the other out
+        let submitted = dispute.evidence.iter().filter(|evidence| evidence.submitter == party).count();
+        require!(submitted < MAX_DISPUTE_EVIDENCE_PER_PARTY, EscrowError::EvidenceLimitReached);
+
+        let now = Clock::get()?.unix_timestamp;
+        dispute.evidence.push(DisputeEvidence {
+            submitter: party,
+            content_hash,
+            submitted_at: now,
+        });
+
+        emit!(EvidenceSubmitted {
+            escrow_id: escrow_state.key(),
+            submitter: party,
+            content_hash,
+            index: (dispute.evidence.len() - 1) as u8,
+            timestamp: now,
+        });
+
+        msg!("✅ Evidence submitted - Escrow: {}, By: {}, Seed: {}", escrow_state.key(), party, transaction_seed);
+        Ok(())
+    }
+
+    /// Settles a disputed escrow in full to the seller or back to the buyer (minus fee) -
+    /// only callable by the arbiter. The dispute account is closed to whoever opened it.
+    pub fn resolve_dispute(ctx: Context<ResolveDispute>, transaction_seed: u64, release_to_seller: bool) -> Result<()> {
+        let settlement = &mut ctx.accounts.settlement;
+        require_keys_eq!(settlement.caller.key(), settlement.config.arbiter, EscrowError::NotArbiter);
+
+        let amount = if release_to_seller {
+            settlement.release_to_seller(transaction_seed, CompletionAction::DisputeReleased)?
+        } else {
+            settlement.refund_to_buyer(transaction_seed, CompletionAction::DisputeRefunded)?
+        };
+
+        let escrow_state = &settlement.escrow_state;
+        emit!(DisputeResolved {
+            escrow_id: escrow_state.key(),
+            arbiter: settlement.caller.key(),
+            released_to_seller: release_to_seller,
+            amount,
+            evidence_count: ctx.accounts.dispute.evidence.len() as u8,
+            timestamp: escrow_state.completed_at,
+        });
+
+        msg!("✅ Dispute resolved - Escrow: {}, Released to seller: {}, Amount: {}, Seed: {}", 
+            escrow_state.key(), release_to_seller, amount, transaction_seed);
+        Ok(())
+    }
+
+    /// Pays part of the held amount to the seller and refunds the rest to the buyer (buyer fee is kept) -
+    /// callable by the marketplace authority, or by the arbiter while the escrow is disputed.
+    /// The seller's share is an absolute amount or basis points of the held amount; the seller fee
+    /// is charged pro rata on that share only.
+    pub fn settle_split(ctx: Context<SettleSplit>, transaction_seed: u64, seller_share: SplitShare) -> Result<()> {
+        let accounts = &ctx.accounts;
+        let escrow_state = &accounts.escrow_state;
+        let disputed = escrow_state.stage == EscrowStage::Disputed;
+
+        // --- Strict Authorization ---
+        if disputed {
+            require_keys_eq!(accounts.caller.key(), accounts.config.arbiter, EscrowError::NotArbiter);
+        } else {
+            require_keys_eq!(accounts.caller.key(), accounts.config.authority, EscrowError::Unauthorized);
+        }
+
+        // --- State Validation ---
+        require!(escrow_state.is_initialized, EscrowError::NotInitialized);
+        CompletionAction::Split.check_stage(escrow_state.stage)?;
+        require_keys_eq!(accounts.seller.key(), escrow_state.seller, EscrowError::RecipientNotSeller);
+        require_keys_eq!(accounts.buyer.key(), escrow_state.buyer, EscrowError::RecipientNotBuyer);
+
+        let seller_amount = seller_share.seller_amount(escrow_state.amount_for_seller)?;
+        if seller_amount > 0 {
+            accounts.config.check_not_paused(PAUSE_RELEASE)?;
+        }
+        let buyer_amount = escrow_state.amount_for_seller.checked_sub(seller_amount)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        let seller_fee_amount = (escrow_state.seller_fee_amount as u128)
+            .checked_mul(seller_amount as u128)
+            .and_then(|x| x.checked_div(escrow_state.amount_for_seller as u128))
+            .and_then(|x| u64::try_from(x).ok())
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        let seller_amount = seller_amount.checked_sub(seller_fee_amount)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+
+        // --- Transfers ---
+        if seller_fee_amount > 0 {
+            let fee_token_accounts = EscrowTokenAccounts::from_optional(
+                &accounts.mint,
+                &accounts.vault_token_account,
+                &accounts.fee_token_account,
+                &accounts.token_program,
+                &accounts.memo_program,
+            );
+            pay_from_escrow(
+                escrow_state,
+                transaction_seed,
+                &accounts.vault,
+                &accounts.system_program,
+                &accounts.fee_vault.to_account_info(),
+                fee_token_accounts,
+                seller_fee_amount,
+            )?;
+        }
+        if seller_amount > 0 {
+            let seller_token_accounts = EscrowTokenAccounts::from_optional(
+                &accounts.mint,
+                &accounts.vault_token_account,
+                &accounts.seller_token_account,
+                &accounts.token_program,
+                &accounts.memo_program,
+            );
+            pay_from_escrow(
+                escrow_state,
+                transaction_seed,
+                &accounts.vault,
+                &accounts.system_program,
+                &accounts.seller,
+                seller_token_accounts,
+                seller_amount,
+            )?;
+        }
+        if buyer_amount > 0 {
+            let buyer_token_accounts = EscrowTokenAccounts::from_optional(
+                &accounts.mint,
+                &accounts.vault_token_account,
+                &accounts.buyer_token_account,
+                &accounts.token_program,
+                &accounts.memo_program,
+            );
+            pay_from_escrow(
+                escrow_state,
+                transaction_seed,
+                &accounts.vault,
+                &accounts.system_program,
+                &accounts.buyer,
+                buyer_token_accounts,
+                buyer_amount,
+            )?;
+        }
+
+        // --- Close Dispute ---
+        if disputed {
+            let (dispute, dispute_opener) = match (&accounts.dispute, &accounts.dispute_opener) {
+                (Some(dispute), Some(dispute_opener)) => (dispute, dispute_opener),
+                _ => return err!(EscrowError::MissingDisputeAccount),
+            };
+            require_keys_eq!(dispute_opener.key(), dispute.opened_by, EscrowError::NotEscrowParty);
+            dispute.close(dispute_opener.to_account_info())?;
+        }
+
+        // --- Update State ---
+        let escrow_state = &mut ctx.accounts.escrow_state;
+        escrow_state.stage = EscrowStage::Split;
+        escrow_state.seller_settled_amount = seller_amount;
+        escrow_state.buyer_refunded_amount = buyer_amount;
+        escrow_state.seller_fee_amount = seller_fee_amount;
+        escrow_state.completed_at = Clock::get()?.unix_timestamp;
+
+        emit!(EscrowCompleted {
+            escrow_id: escrow_state.key(),
+            buyer: escrow_state.buyer,
+            seller: escrow_state.seller,
+            amount: seller_amount,
+            buyer_fee: escrow_state.buyer_fee_amount,
+            seller_fee: seller_fee_amount,
+            action: CompletionAction::Split.as_str().to_string(),
+            timestamp: escrow_state.completed_at,
+        });
+
+        emit!(EscrowSplitSettled {
+            escrow_id: escrow_state.key(),
+            seller_amount,
+            buyer_amount,
+            buyer_fee_kept: escrow_state.buyer_fee_amount,
+            seller_fee: seller_fee_amount,
+            timestamp: escrow_state.completed_at,
+        });
+
+        msg!("✅ Escrow split - Seller: {} gets {}, Buyer: {} gets {}, Seed: {}", 
+            escrow_state.seller, seller_amount, escrow_state.buyer, buyer_amount, transaction_seed);
+        Ok(())
+    }
+
+    /// Cancels escrow and refunds buyer (buyer fee is kept once the seller has accepted) - only callable by marketplace authority
+    pub fn cancel_escrow_and_refund_buyer(ctx: Context<ProcessEscrow>, transaction_seed: u64) -> Result<()> {
+        // --- Strict Authorization ---
+        require_keys_eq!(ctx.accounts.caller.key(), ctx.accounts.config.authority, EscrowError::Unauthorized);
+
+        let amount_to_refund = ctx.accounts.refund_to_buyer(transaction_seed, CompletionAction::Cancelled)?;
+
+        msg!("✅ Escrow cancelled, buyer refunded: {} - Amount: {} (Buyer fee: {} kept), Seed: {}", 
+            ctx.accounts.escrow_state.buyer, amount_to_refund, ctx.accounts.escrow_state.buyer_fee_amount, transaction_seed);
+        Ok(())
+    }
+
+    /// Moves an escrow created by the original program under the seed-only address
+    /// (`[b"escrow", seed]`) to its buyer-bound address - callable by anyone.
+    /// Those escrows kept the lamports in the escrow account itself and had already paid
+    /// the fee, so the held amount moves into a fresh vault and the old account is closed.
+    /// The caller pays for the new escrow state and receives the old account's leftover rent.
+    pub fn migrate_escrow(ctx: Context<MigrateEscrow>, transaction_seed: u64) -> Result<()> {
+        let legacy_info = ctx.accounts.legacy_escrow.to_account_info();
+        let legacy = LegacyEscrowState::try_from_account(&legacy_info)?;
+        require!(legacy.is_initialized, EscrowError::NotInitialized);
+        require_keys_eq!(ctx.accounts.buyer.key(), legacy.buyer, EscrowError::RecipientNotBuyer);
+
+        // --- Copy State ---
+        let escrow_state = &mut ctx.accounts.escrow_state;
+        escrow_state.buyer = legacy.buyer;
+        escrow_state.seller = legacy.seller;
+        escrow_state.marketplace_authority = legacy.marketplace_authority;
+        escrow_state.total_initial_amount = legacy.total_initial_amount;
+        // The original program took its fee out of the buyer's deposit at funding
+        escrow_state.buyer_fee_amount = legacy.fee_amount;
+        escrow_state.amount_for_seller = legacy.amount_for_seller;
+        escrow_state.mint = None;
+        escrow_state.is_initialized = true;
+        escrow_state.bump = ctx.bumps.escrow_state;
+        escrow_state.vault_bump = ctx.bumps.vault;
+        escrow_state.created_at = legacy.created_at;
+        escrow_state.completed_at = legacy.completed_at;
+        let held = match legacy.stage {
+            EscrowStage::Funded => {
+                // There was no acceptance step or deadlines, so the order counts as accepted now
+                escrow_state.stage = EscrowStage::Accepted;
+                escrow_state.set_deadlines(&ctx.accounts.config, Clock::get()?.unix_timestamp)?;
+                legacy.amount_for_seller
+            }
+            EscrowStage::Released => {
+                escrow_state.stage = EscrowStage::Released;
+                escrow_state.seller_settled_amount = legacy.amount_for_seller;
+                0
+            }
+            EscrowStage::Cancelled => {
+                escrow_state.stage = EscrowStage::Cancelled;
+                escrow_state.buyer_refunded_amount = legacy.amount_for_seller;
+                0
+            }
+            _ => return err!(EscrowError::InvalidLegacyEscrow),
+        };
+
+        // --- Move Held Lamports ---
+        // The legacy escrow is program-owned, so its lamports are moved directly
+        if held > 0 {
+            let vault_deposit = held
+                .checked_add(Rent::get()?.minimum_balance(0))
+                .ok_or(EscrowError::ArithmeticOverflow)?;
+            legacy_info.sub_lamports(vault_deposit)?;
+            ctx.accounts.vault.add_lamports(vault_deposit)?;
+        }
+
+        // --- Close Legacy Escrow ---
+        let leftover = legacy_info.lamports();
+        legacy_info.sub_lamports(leftover)?;
+        ctx.accounts.caller.add_lamports(leftover)?;
+        legacy_info.assign(&System::id());
+        legacy_info.resize(0)?;
+
+        emit!(EscrowMigrated {
+            legacy_escrow_id: legacy_info.key(),
+            escrow_id: ctx.accounts.escrow_state.key(),
+            buyer: legacy.buyer,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("✅ Escrow migrated - Legacy: {}, New: {}, Held: {}, Seed: {}", 
+            legacy_info.key(), ctx.accounts.escrow_state.key(), held, transaction_seed);
+        Ok(())
+    }
+
+    /// Closes a settled escrow once the retention window has passed - callable by anyone.
+    /// Rent for the escrow state and vault goes back to the buyer who paid it. Transfer fees
+    /// withheld in a Token-2022 vault are harvested to the mint first so the vault can close.
+    pub fn close_escrow(ctx: Context<CloseEscrow>, transaction_seed: u64) -> Result<()> {
+        let escrow_state = &ctx.accounts.escrow_state;
+
+        // --- State Validation ---
+        require!(escrow_state.stage.is_settled(), EscrowError::EscrowNotSettled);
+        let closed_at = Clock::get()?.unix_timestamp;
+        let closable_at = escrow_state.completed_at
+            .checked_add(ctx.accounts.config.escrow_retention_period)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        require!(closed_at >= closable_at, EscrowError::RetentionPeriodActive);
+
+        // --- Return Vault Rent to Buyer ---
+        let vault_lamports = ctx.accounts.vault.lamports();
+        if vault_lamports > 0 {
+            transfer_from_vault(
+                escrow_state,
+                &ctx.accounts.vault,
+                &ctx.accounts.buyer,
+                &ctx.accounts.system_program,
+                vault_lamports,
+            )?;
+        }
+        let mut rent_returned = vault_lamports
+            .checked_add(escrow_state.to_account_info().lamports())
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+
+        // --- Close Token Vault ---
+        if let Some(mint) = escrow_state.mint {
+            let (vault_token_account, token_program) = match (&ctx.accounts.vault_token_account, &ctx.accounts.token_program) {
+                (Some(vault_token_account), Some(token_program)) => (vault_token_account, token_program),
+                _ => return err!(EscrowError::MissingTokenAccounts),
+            };
+            require_keys_eq!(
+                vault_token_account.key(),
+                get_associated_token_address_with_program_id(&escrow_state.key(), &mint, &token_program.key()),
+                EscrowError::InvalidVaultTokenAccount
+            );
+            rent_returned = rent_returned
+                .checked_add(vault_token_account.to_account_info().lamports())
+                .ok_or(EscrowError::ArithmeticOverflow)?;
+
+            // Transfer fees withheld in the vault block closing it, so move them to the mint first
+            if has_withheld_transfer_fees(&vault_token_account.to_account_info())? {
+                let mint_account = ctx.accounts.mint.as_ref().ok_or(EscrowError::MissingTokenAccounts)?;
+                require_keys_eq!(mint_account.key(), mint, EscrowError::MintMismatch);
+                let cpi_accounts = HarvestWithheldTokensToMint {
+                    token_program_id: token_program.to_account_info(),
+                    mint: mint_account.to_account_info(),
+                };
+                harvest_withheld_tokens_to_mint(
+                    CpiContext::new(token_program.to_account_info(), cpi_accounts),
+                    vec![vault_token_account.to_account_info()],
+                )?;
+            }
+
+            let bump_seed = escrow_state.bump;
+            let seeds = &[
+                b"escrow".as_ref(),
+                escrow_state.buyer.as_ref(),
+                &transaction_seed.to_le_bytes(),
+                &[bump_seed],
+            ];
+            let signer_seeds = &[&seeds[..]];
+            let cpi_accounts = CloseAccount {
+                account: vault_token_account.to_account_info(),
+                destination: ctx.accounts.buyer.to_account_info(),
+                authority: escrow_state.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, signer_seeds);
+            close_account(cpi_ctx)?;
+        }
+
+        // Escrow state itself is closed to the buyer by the `close` constraint
+        emit!(EscrowArchived {
+            escrow_id: escrow_state.key(),
+            buyer: escrow_state.buyer,
+            seller: escrow_state.seller,
+            marketplace_authority: escrow_state.marketplace_authority,
+            total_initial_amount: escrow_state.total_initial_amount,
+            buyer_fee_amount: escrow_state.buyer_fee_amount,
+            seller_fee_amount: escrow_state.seller_fee_amount,
+            amount_for_seller: escrow_state.amount_for_seller,
+            seller_settled_amount: escrow_state.seller_settled_amount,
+            buyer_refunded_amount: escrow_state.buyer_refunded_amount,
+            stage: escrow_state.stage,
+            created_at: escrow_state.created_at,
+            completed_at: escrow_state.completed_at,
+            rent_returned,
+            timestamp: closed_at,
+        });
+
+        msg!("✅ Escrow closed - Buyer: {}, Rent returned: {}, Seed: {}", 
+            escrow_state.buyer, rent_returned, transaction_seed);
+        Ok(())
+    }
+}
+
+// --- Helpers ---
+
+/// Decodes a program account that may not have been created yet, returning `None` if so.
+/// Callers pin its address with a seeds constraint, so the buyer can't leave it out.
+fn load_if_initialized<T: AccountDeserialize>(account: &AccountInfo) -> Result<Option<T>> {
+    if account.owner != &crate::ID || account.data_is_empty() {
+        return Ok(None);
+    }
+    let data = account.try_borrow_data()?;
+    Ok(Some(T::try_deserialize(&mut &data[..])?))
+}
+
+/// Computes a marketplace fee on `amount` at `fee_basis_points`.
+/// A non-zero rate must yield a non-zero fee, so tiny escrows can't dodge fees by rounding.
+fn calculate_fee(amount: u64, fee_basis_points: u16) -> Result<u64> {
+    let fee_amount = amount
+        .checked_mul(fee_basis_points as u64)
+        .and_then(|x| x.checked_div(10000))
+        .ok_or(EscrowError::ArithmeticOverflow)?;
+
+    require!(fee_basis_points == 0 || fee_amount > 0, EscrowError::FeeTooSmall);
+    Ok(fee_amount)
+}
+
+fn validate_config_params(
+    max_fee_basis_points: u16,
+    min_escrow_amount: u64,
+    min_net_amount: u64,
+    escrow_retention_period: i64,
+) -> Result<()> {
+    require!(max_fee_basis_points > 0 && max_fee_basis_points <= MAX_FEE_BASIS_POINTS, EscrowError::InvalidFeeBasisPoints);
+    require!(min_net_amount > 0 && min_net_amount <= min_escrow_amount, EscrowError::InvalidMinimumAmounts);
+    require!(escrow_retention_period >= 0, EscrowError::InvalidRetentionPeriod);
+    Ok(())
+}
+
+/// Pays lamports out of an escrow's vault PDA, signing with the vault seeds.
+fn transfer_from_vault<'info>(
+    escrow_state: &Account<'info, EscrowState>,
+    vault: &SystemAccount<'info>,
+    recipient: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    amount: u64,
+) -> Result<()> {
+    let escrow_key = escrow_state.key();
+    let seeds = &[
+        b"vault".as_ref(),
+        escrow_key.as_ref(),
+        &[escrow_state.vault_bump],
+    ];
+    let signer_seeds = &[&seeds[..]];
+    let cpi_accounts = Transfer {
+        from: vault.to_account_info(),
+        to: recipient.clone(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(system_program.to_account_info(), cpi_accounts, signer_seeds);
+    transfer(cpi_ctx, amount)
+}
+
+/// Pays tokens out of an escrow's vault token account, signing with the escrow seeds.
+/// The vault must be the escrow's associated token account and the recipient token
+/// account must belong to `recipient` and hold the escrow's mint.
+fn transfer_tokens_from_vault<'info>(
+    escrow_state: &Account<'info, EscrowState>,
+    transaction_seed: u64,
+    token_accounts: &EscrowTokenAccounts<'_, 'info>,
+    recipient: Pubkey,
+    amount: u64,
+) -> Result<()> {
+    let mint = escrow_state.mint.ok_or(EscrowError::MintMismatch)?;
+    let EscrowTokenAccounts {
+        mint: mint_account,
+        vault_token_account,
+        recipient_token_account,
+        token_program,
+        memo_program,
+    } = token_accounts;
+
+    require_keys_eq!(mint_account.key(), mint, EscrowError::MintMismatch);
+    require_keys_eq!(
+        vault_token_account.key(),
+        get_associated_token_address_with_program_id(&escrow_state.key(), &mint, &token_program.key()),
+        EscrowError::InvalidVaultTokenAccount
+    );
+    require_keys_eq!(recipient_token_account.mint, mint, EscrowError::MintMismatch);
+    require_keys_eq!(recipient_token_account.owner, recipient, EscrowError::InvalidRecipientTokenAccount);
+
+    memo_if_required(&recipient_token_account.to_account_info(), *memo_program, escrow_state.key())?;
+
+    let bump_seed = escrow_state.bump;
+    let seeds = &[
+        b"escrow".as_ref(),
+        escrow_state.buyer.as_ref(),
+        &transaction_seed.to_le_bytes(),
+        &[bump_seed],
+    ];
+    let signer_seeds = &[&seeds[..]];
+    let cpi_accounts = TransferChecked {
+        from: vault_token_account.to_account_info(),
+        mint: mint_account.to_account_info(),
+        to: recipient_token_account.to_account_info(),
+        authority: escrow_state.to_account_info(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, signer_seeds);
+    transfer_checked(cpi_ctx, amount, mint_account.decimals)
+}
+
+/// Fee rates resolved for a new escrow, and how they were arrived at
+struct FeeRates {
+    buyer_fee_basis_points: u16,
+    seller_fee_basis_points: u16,
+    fee_tier: Option<u8>,
+    fee_override_applied: bool,
+    fee_discount_applied: bool,
+}
+
+/// Token accounts needed to pay out of an SPL token escrow
+struct EscrowTokenAccounts<'a, 'info> {
+    mint: &'a InterfaceAccount<'info, Mint>,
+    vault_token_account: &'a InterfaceAccount<'info, TokenAccount>,
+    recipient_token_account: &'a InterfaceAccount<'info, TokenAccount>,
+    token_program: &'a Interface<'info, TokenInterface>,
+    memo_program: Option<&'a Program<'info, Memo>>,
+}
+
+impl<'a, 'info> EscrowTokenAccounts<'a, 'info> {
+    /// Collects the optional token accounts of a settlement instruction, if all were passed
+    fn from_optional(
+        mint: &'a Option<InterfaceAccount<'info, Mint>>,
+        vault_token_account: &'a Option<InterfaceAccount<'info, TokenAccount>>,
+        recipient_token_account: &'a Option<InterfaceAccount<'info, TokenAccount>>,
+        token_program: &'a Option<Interface<'info, TokenInterface>>,
+        memo_program: &'a Option<Program<'info, Memo>>,
+    ) -> Option<Self> {
+        Some(EscrowTokenAccounts {
+            mint: mint.as_ref()?,
+            vault_token_account: vault_token_account.as_ref()?,
+            recipient_token_account: recipient_token_account.as_ref()?,
+            token_program: token_program.as_ref()?,
+            memo_program: memo_program.as_ref(),
+        })
+    }
+}
+
+/// Pays `amount` in the escrow's currency: lamports from the vault PDA for SOL escrows,
+/// or tokens from the vault token account for token escrows.
+fn pay_from_escrow<'info>(
+    escrow_state: &Account<'info, EscrowState>,
+    transaction_seed: u64,
+    vault: &SystemAccount<'info>,
+    system_program: &Program<'info, System>,
+    recipient: &AccountInfo<'info>,
+    token_accounts: Option<EscrowTokenAccounts<'_, 'info>>,
+    amount: u64,
+) -> Result<()> {
+    if escrow_state.mint.is_none() {
+        return transfer_from_vault(escrow_state, vault, recipient, system_program, amount);
+    }
+
+    let token_accounts = token_accounts.ok_or(EscrowError::MissingTokenAccounts)?;
+    transfer_tokens_from_vault(escrow_state, transaction_seed, &token_accounts, recipient.key(), amount)
+}
+
+/// Emits `ListingUpdated` with the listing's current price, inventory and status
+fn emit_listing_updated(listing: &Account<Listing>) -> Result<()> {
+    emit!(ListingUpdated {
+        listing: listing.key(),
+        price: listing.price,
+        quantity: listing.quantity,
+        status: listing.status,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+    Ok(())
+}
+
+/// Checks a Merkle proof for `leaf` against `root`. Internal nodes hash a `1` prefix and the
+/// sorted pair of children (leaves use a `0` prefix), so proofs need no left/right flags.
+fn verify_merkle_proof(proof: &[[u8; 32]], root: [u8; 32], leaf: [u8; 32]) -> bool {
+    let computed = proof.iter().fold(leaf, |node, sibling| {
+        let (first, second) = if node <= *sibling { (node, *sibling) } else { (*sibling, node) };
+        hashv(&[&[1u8], &first, &second]).to_bytes()
+    });
+    computed == root
+}
+
+/// Issues a memo CPI when a Token-2022 account requires memos on incoming transfers.
+/// Token-2022 checks the instruction right before the transfer, so call this last.
+fn memo_if_required<'info>(
+    token_account: &AccountInfo<'info>,
+    memo_program: Option<&Program<'info, Memo>>,
+    reference: Pubkey,
+) -> Result<()> {
+    if token_account.owner != &anchor_spl::token_2022::ID {
+        return Ok(());
+    }
+    let required = {
+        let data = token_account.try_borrow_data()?;
+        let state = StateWithExtensions::<SplTokenAccount>::unpack(&data)?;
+        memo_required(&state)
+    };
+    if !required {
+        return Ok(());
+    }
+
+    let memo_program = memo_program.ok_or(EscrowError::MemoProgramRequired)?;
+    let memo = format!("SolZone {}", reference);
+    build_memo(CpiContext::new(memo_program.to_account_info(), BuildMemo {}), memo.as_bytes())
+}
+
+/// Whether a Token-2022 mint has a permanent delegate able to move tokens out of any account
+fn has_permanent_delegate(mint: &AccountInfo) -> Result<bool> {
+    if mint.owner != &anchor_spl::token_2022::ID {
+        return Ok(false);
+    }
+    let data = mint.try_borrow_data()?;
+    let state = StateWithExtensions::<SplMint>::unpack(&data)?;
+    Ok(state
+        .get_extension::<PermanentDelegate>()
+        .map(|extension| Option::<Pubkey>::from(extension.delegate).is_some())
+        .unwrap_or(false))
+}
+
+/// Whether a Token-2022 account holds transfer fees withheld from incoming transfers
+fn has_withheld_transfer_fees(token_account: &AccountInfo) -> Result<bool> {
+    if token_account.owner != &anchor_spl::token_2022::ID {
+        return Ok(false);
+    }
+    let data = token_account.try_borrow_data()?;
+    let state = StateWithExtensions::<SplTokenAccount>::unpack(&data)?;
+    Ok(state
+        .get_extension::<TransferFeeAmount>()
+        .map(|extension| u64::from(extension.withheld_amount) > 0)
+        .unwrap_or(false))
+}
+
+impl<'info> InitializeEscrow<'info> {
+    /// Takes the buyer's lamport deposit and buyer fee into the vault, then fills in the escrow state.
+    /// The buyer fee stays in the vault and the deadlines stay unset until the seller accepts.
+    fn fund(
+        &mut self,
+        bumps: &InitializeEscrowBumps,
+        transaction_seed: u64,
+        total_amount_to_escrow: u64,
+        order_reference: [u8; 32],
+        stage: EscrowStage,
+    ) -> Result<()> {
+        let config = &self.config;
+
+        // --- Enhanced Validation ---
+        config.check_not_paused(PAUSE_CREATE)?;
+        require!(total_amount_to_escrow > 0, EscrowError::ZeroAmount);
+        require!(total_amount_to_escrow >= config.min_escrow_amount, EscrowError::MinimumAmount);
+
+        let escrow_state = &mut self.escrow_state;
+        let buyer = &self.buyer;
+        let seller = &self.seller; 
+
+        // --- Fee Calculation with Safety ---
+        let fee_schedule = load_if_initialized::<FeeSchedule>(&self.fee_schedule)?;
+        let seller_fee_override = load_if_initialized::<SellerFeeOverride>(&self.seller_fee_override)?;
+        let fee_rates = config.fee_rates(
+            Some(total_amount_to_escrow),
+            fee_schedule.as_ref(),
+            seller_fee_override.as_ref(),
+            buyer.key(),
+            self.holder_token_account.as_ref(),
+        );
+        let buyer_fee_amount = calculate_fee(total_amount_to_escrow, fee_rates.buyer_fee_basis_points)?;
+        let seller_fee_amount = calculate_fee(total_amount_to_escrow, fee_rates.seller_fee_basis_points)?;
+        require!(total_amount_to_escrow > seller_fee_amount, EscrowError::AmountLessThanFee);
+
+        // --- Validate Minimum Net Amount ---
+        let seller_net_amount = total_amount_to_escrow.checked_sub(seller_fee_amount)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        require!(seller_net_amount >= config.min_net_amount, EscrowError::NetAmountTooSmall);
+
+        // --- SOL Transfers ---
+        // Funds are held in a data-less, system-owned vault PDA so the System Program can
+        // move them out later. The buyer also covers the vault's rent-exempt minimum.
+        // Transfer escrow amount, buyer fee and vault rent from buyer to vault PDA
+        let vault_rent = Rent::get()?.minimum_balance(0);
+        let vault_deposit = total_amount_to_escrow.checked_add(buyer_fee_amount)
+            .and_then(|x| x.checked_add(vault_rent))
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        let cpi_accounts_buyer_to_vault = Transfer {
+            from: buyer.to_account_info(),
+            to: self.vault.to_account_info(),
+        };
+        let cpi_program_buyer_to_vault = self.system_program.to_account_info();
+        let cpi_ctx_buyer_to_vault = CpiContext::new(cpi_program_buyer_to_vault, cpi_accounts_buyer_to_vault);
+        transfer(cpi_ctx_buyer_to_vault, vault_deposit)?;
+
+        // --- Initialize Escrow State ---
+        escrow_state.buyer = buyer.key();
+        escrow_state.seller = seller.key();
+        escrow_state.marketplace_authority = config.authority;
+        escrow_state.total_initial_amount = total_amount_to_escrow;
+        escrow_state.buyer_fee_amount = buyer_fee_amount;
+        escrow_state.amount_for_seller = total_amount_to_escrow;
+        escrow_state.buyer_fee_basis_points = fee_rates.buyer_fee_basis_points;
+        escrow_state.seller_fee_basis_points = fee_rates.seller_fee_basis_points;
+        escrow_state.seller_fee_amount = seller_fee_amount;
+        escrow_state.fee_discount_applied = fee_rates.fee_discount_applied;
+        escrow_state.fee_tier = fee_rates.fee_tier;
+        escrow_state.fee_override_applied = fee_rates.fee_override_applied;
+        escrow_state.order_reference = order_reference;
+        escrow_state.mint = None;
+        escrow_state.stage = stage;
+        escrow_state.is_initialized = true;
+        escrow_state.bump = bumps.escrow_state;
+        escrow_state.vault_bump = bumps.vault;
+        escrow_state.created_at = Clock::get()?.unix_timestamp;
+
+        emit!(EscrowCreated {
+            escrow_id: escrow_state.key(),
+            buyer: buyer.key(),
+            seller: seller.key(),
+            mint: None,
+            amount: total_amount_to_escrow,
+            buyer_fee: buyer_fee_amount,
+            seller_fee: seller_fee_amount,
+            fee_discount_applied: fee_rates.fee_discount_applied,
+            order_reference,
+            timestamp: escrow_state.created_at,
+        });
+
+        msg!("✅ Escrow initialized - Buyer: {}, Seller: {}, Amount: {}, Buyer fee: {}, Seller fee: {}, Seed: {}", 
+            escrow_state.buyer, escrow_state.seller, total_amount_to_escrow, buyer_fee_amount, seller_fee_amount, transaction_seed);
+        Ok(())
+    }
+}
+
+impl<'info> InitializeTokenEscrow<'info> {
+    /// Token counterpart of `InitializeEscrow::fund`; holds what the vault token account actually received
+    fn fund(
+        &mut self,
+        bumps: &InitializeTokenEscrowBumps,
+        transaction_seed: u64,
+        total_amount_to_escrow: u64,
+        order_reference: [u8; 32],
+    ) -> Result<()> {
+        let config = &self.config;
+
+        // --- Enhanced Validation ---
+        config.check_not_paused(PAUSE_CREATE)?;
+        // Minimum amounts in config are denominated in lamports, so they don't apply here
+        require!(total_amount_to_escrow > 0, EscrowError::ZeroAmount);
+
+        // --- Mint Extension Policy ---
+        let mint = &self.mint;
+        if config.reject_permanent_delegate {
+            require!(!has_permanent_delegate(&mint.to_account_info())?, EscrowError::PermanentDelegateNotAllowed);
+        }
+
+        // --- Fee Calculation with Safety ---
+        // Fee tiers are denominated in lamports, so only the seller override and holder discount apply
+        let seller_fee_override = load_if_initialized::<SellerFeeOverride>(&self.seller_fee_override)?;
+        let fee_rates = config.fee_rates(
+            None,
+            None,
+            seller_fee_override.as_ref(),
+            self.buyer.key(),
+            self.holder_token_account.as_ref(),
+        );
+        let buyer_fee_amount = calculate_fee(total_amount_to_escrow, fee_rates.buyer_fee_basis_points)?;
+
+        // --- Token Transfers ---
+        let token_program = self.token_program.to_account_info();
+        let vault_balance_before = self.vault_token_account.amount;
+
+        // 1. Transfer escrow amount from buyer to escrow vault token account
+        let cpi_accounts_buyer_to_vault = TransferChecked {
+            from: self.buyer_token_account.to_account_info(),
+            mint: mint.to_account_info(),
+            to: self.vault_token_account.to_account_info(),
+            authority: self.buyer.to_account_info(),
+        };
+        let cpi_ctx_buyer_to_vault = CpiContext::new(token_program.clone(), cpi_accounts_buyer_to_vault);
+        transfer_checked(cpi_ctx_buyer_to_vault, total_amount_to_escrow, mint.decimals)?;
+
+        // Transfer-fee mints withhold part of the deposit, so hold only what actually arrived
+        self.vault_token_account.reload()?;
+        let vault_balance_after_deposit = self.vault_token_account.amount;
+        let amount_for_seller = vault_balance_after_deposit
+            .checked_sub(vault_balance_before)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        let seller_fee_amount = calculate_fee(amount_for_seller, fee_rates.seller_fee_basis_points)?;
+        require!(amount_for_seller > seller_fee_amount, EscrowError::AmountLessThanFee);
+
+        // 2. Transfer buyer fee from buyer to escrow vault token account, held there until the seller accepts
+        let mut held_buyer_fee = 0;
+        if buyer_fee_amount > 0 {
+            let cpi_accounts_buyer_fee_to_vault = TransferChecked {
+                from: self.buyer_token_account.to_account_info(),
+                mint: mint.to_account_info(),
+                to: self.vault_token_account.to_account_info(),
+                authority: self.buyer.to_account_info(),
+            };
+            let cpi_ctx_buyer_fee_to_vault = CpiContext::new(token_program, cpi_accounts_buyer_fee_to_vault);
+            transfer_checked(cpi_ctx_buyer_fee_to_vault, buyer_fee_amount, mint.decimals)?;
+
+            self.vault_token_account.reload()?;
+            held_buyer_fee = self.vault_token_account.amount
+                .checked_sub(vault_balance_after_deposit)
+                .ok_or(EscrowError::ArithmeticOverflow)?;
+        }
+
+        // --- Initialize Escrow State ---
+        let escrow_state = &mut self.escrow_state;
+        escrow_state.buyer = self.buyer.key();
+        escrow_state.seller = self.seller.key();
+        escrow_state.marketplace_authority = config.authority;
+        escrow_state.total_initial_amount = total_amount_to_escrow;
+        escrow_state.buyer_fee_amount = held_buyer_fee;
+        escrow_state.amount_for_seller = amount_for_seller;
+        escrow_state.buyer_fee_basis_points = fee_rates.buyer_fee_basis_points;
+        escrow_state.seller_fee_basis_points = fee_rates.seller_fee_basis_points;
+        escrow_state.seller_fee_amount = seller_fee_amount;
+        escrow_state.fee_discount_applied = fee_rates.fee_discount_applied;
+        escrow_state.fee_tier = fee_rates.fee_tier;
+        escrow_state.fee_override_applied = fee_rates.fee_override_applied;
+        escrow_state.order_reference = order_reference;
+        escrow_state.mint = Some(mint.key());
+        escrow_state.stage = EscrowStage::Funded;
+        escrow_state.is_initialized = true;
+        escrow_state.bump = bumps.escrow_state;
+        escrow_state.vault_bump = bumps.vault;
+        escrow_state.created_at = Clock::get()?.unix_timestamp;
+
+        emit!(EscrowCreated {
+            escrow_id: escrow_state.key(),
+            buyer: escrow_state.buyer,
+            seller: escrow_state.seller,
+            mint: escrow_state.mint,
+            amount: amount_for_seller,
+            buyer_fee: held_buyer_fee,
+            seller_fee: seller_fee_amount,
+            fee_discount_applied: fee_rates.fee_discount_applied,
+            order_reference,
+            timestamp: escrow_state.created_at,
+        });
+
+        msg!("✅ Token escrow initialized - Buyer: {}, Seller: {}, Mint: {}, Amount: {}, Buyer fee: {}, Seller fee: {}, Seed: {}", 
+            escrow_state.buyer, escrow_state.seller, mint.key(), amount_for_seller, held_buyer_fee, seller_fee_amount, transaction_seed);
+        Ok(())
+    }
+}
+
+impl<'info> RespondToOffer<'info> {
+    /// Moves an offer to `Accepted`: sends the held buyer fee to the fee vault, clears any
+    /// counter-offer, starts the deadlines and emits `OfferAccepted`
+    fn accept(&mut self) -> Result<()> {
+        let escrow_state = &self.escrow_state;
+        require!(escrow_state.stage == EscrowStage::Offered, EscrowError::NotOffered);
+
+        if escrow_state.buyer_fee_amount > 0 {
+            transfer_from_vault(
+                escrow_state,
+                &self.vault,
+                &self.fee_vault.to_account_info(),
+                &self.system_program,
+                escrow_state.buyer_fee_amount,
+            )?;
+        }
+
+        let now = Clock::get()?.unix_timestamp;
+        let escrow_state = &mut self.escrow_state;
+        escrow_state.stage = EscrowStage::Accepted;
+        escrow_state.counter_amount = 0;
+        escrow_state.set_deadlines(&self.config, now)?;
+
+        emit!(OfferAccepted {
+            escrow_id: escrow_state.key(),
+            buyer: escrow_state.buyer,
+            seller: escrow_state.seller,
+            amount: escrow_state.amount_for_seller,
+            buyer_fee: escrow_state.buyer_fee_amount,
+            seller_fee: escrow_state.seller_fee_amount,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    /// Returns the offered amount and held buyer fee to the buyer, marks the escrow cancelled
+    /// and emits `EscrowCompleted` with `action`. Callers check authorization first.
+    fn refund_offer(&mut self, action: CompletionAction) -> Result<u64> {
+        // --- State Validation ---
+        let escrow_state = &self.escrow_state;
+        action.check_stage(escrow_state.stage)?;
+        require_keys_eq!(self.buyer.key(), escrow_state.buyer, EscrowError::RecipientNotBuyer);
+
+        let amount_to_refund = escrow_state.amount_for_seller.checked_add(escrow_state.buyer_fee_amount)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+
+        // --- Transfer to Buyer ---
+        transfer_from_vault(
+            escrow_state,
+            &self.vault,
+            &self.buyer.to_account_info(),
+            &self.system_program,
+            amount_to_refund,
+        )?;
+
+        // --- Update State ---
+        let escrow_state = &mut self.escrow_state;
+        escrow_state.stage = EscrowStage::Cancelled;
+        escrow_state.buyer_refunded_amount = amount_to_refund;
+        escrow_state.buyer_fee_amount = 0;
+        escrow_state.seller_fee_amount = 0;
+        escrow_state.counter_amount = 0;
+        escrow_state.completed_at = Clock::get()?.unix_timestamp;
+
+        emit!(EscrowCompleted {
+            escrow_id: escrow_state.key(),
+            buyer: escrow_state.buyer,
+            seller: escrow_state.seller,
+            amount: amount_to_refund,
+            buyer_fee: 0,
+            seller_fee: 0,
+            action: action.as_str().to_string(),
+            timestamp: escrow_state.completed_at,
+        });
+        Ok(amount_to_refund)
+    }
+}
+
+impl<'info> ProcessEscrow<'info> {
+    /// Pays the held amount minus the seller fee to the seller, sends the seller fee to the
+    /// fee vault, marks the escrow released and emits `EscrowCompleted` with `action`.
+    /// Callers check authorization first.
+    fn release_to_seller(&mut self, transaction_seed: u64, action: CompletionAction) -> Result<u64> {
+        // --- State Validation ---
+        let escrow_state = &self.escrow_state;
+        self.config.check_not_paused(PAUSE_RELEASE)?;
+        require!(escrow_state.is_initialized, EscrowError::NotInitialized);
+        action.check_stage(escrow_state.stage)?;
+        require_keys_eq!(self.recipient_account.key(), escrow_state.seller, EscrowError::RecipientNotSeller);
+
+        let seller_fee_amount = escrow_state.seller_fee_amount;
+        let amount_to_transfer = escrow_state.amount_for_seller.checked_sub(seller_fee_amount)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        require!(amount_to_transfer > 0, EscrowError::ZeroAmount);
+
+        // --- Transfer to Seller and Fee Wallet ---
+        self.pay_out(transaction_seed, amount_to_transfer)?;
+        if seller_fee_amount > 0 {
+            self.collect_fee(transaction_seed, seller_fee_amount)?;
+        }
+
+        // --- Update State ---
+        let escrow_state = &mut self.escrow_state;
+        escrow_state.stage = EscrowStage::Released;
+        escrow_state.seller_settled_amount = amount_to_transfer;
+        escrow_state.completed_at = Clock::get()?.unix_timestamp;
+
+        emit!(EscrowCompleted {
+            escrow_id: escrow_state.key(),
+            buyer: escrow_state.buyer,
+            seller: escrow_state.seller,
+            amount: amount_to_transfer,
+            buyer_fee: escrow_state.buyer_fee_amount,
+            seller_fee: seller_fee_amount,
+            action: action.as_str().to_string(),
+            timestamp: escrow_state.completed_at,
+        });
+        Ok(amount_to_transfer)
+    }
+
+    /// Refunds the held amount to the buyer (the buyer fee is kept once the seller has accepted,
+    /// no seller fee is charged), marks the escrow cancelled and emits `EscrowCompleted` with `action`.
+    /// Callers check authorization first.
+    fn refund_to_buyer(&mut self, transaction_seed: u64, action: CompletionAction) -> Result<u64> {
+        // --- State Validation ---
+        let escrow_state = &self.escrow_state;
+        require!(escrow_state.is_initialized, EscrowError::NotInitialized);
+        action.check_stage(escrow_state.stage)?;
+        require_keys_eq!(self.recipient_account.key(), escrow_state.buyer, EscrowError::RecipientNotBuyer);
+
+        // Unaccepted orders still hold the buyer fee, so it goes back too
+        let held_buyer_fee = if escrow_state.stage == EscrowStage::Funded { escrow_state.buyer_fee_amount } else { 0 };
+        let amount_to_refund = escrow_state.amount_for_seller.checked_add(held_buyer_fee)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        require!(amount_to_refund > 0, EscrowError::ZeroAmount); 
+
+        // --- Transfer to Buyer ---
+        self.pay_out(transaction_seed, amount_to_refund)?;
+
+        // --- Restock Listing ---
+        // An order the seller never accepted gives its units back to the listing it reserved them from
+        if let (EscrowStage::Funded, Some(listing_key)) = (escrow_state.stage, escrow_state.listing) {
+            let listing = self.listing.as_ref().ok_or(EscrowError::MissingListingAccount)?;
+            require_keys_eq!(listing.key(), listing_key, EscrowError::ListingMismatch);
+            Listing::restock_from_refund(listing, escrow_state.listing_quantity)?;
+        }
+
+        // --- Update State ---
+        let escrow_state = &mut self.escrow_state;
+        escrow_state.stage = EscrowStage::Cancelled;
+        escrow_state.buyer_refunded_amount = amount_to_refund;
+        escrow_state.buyer_fee_amount -= held_buyer_fee;
+        escrow_state.seller_fee_amount = 0;
+        escrow_state.completed_at = Clock::get()?.unix_timestamp;
+
+        emit!(EscrowCompleted {
+            escrow_id: escrow_state.key(),
+            buyer: escrow_state.buyer,
+            seller: escrow_state.seller,
+            amount: amount_to_refund,
+            buyer_fee: escrow_state.buyer_fee_amount,
+            seller_fee: 0,
+            action: action.as_str().to_string(),
+            timestamp: escrow_state.completed_at,
+        });
+        Ok(amount_to_refund)
+    }
+
+    /// Pays `amount` to the recipient in the escrow's currency: lamports from the vault PDA
+    /// for SOL escrows, or tokens from the vault token account for token escrows.
+    fn pay_out(&self, transaction_seed: u64, amount: u64) -> Result<()> {
+        let token_accounts = EscrowTokenAccounts::from_optional(
+            &self.mint,
+            &self.vault_token_account,
+            &self.recipient_token_account,
+            &self.token_program,
+            &self.memo_program,
+        );
+        pay_from_escrow(
+            &self.escrow_state,
+            transaction_seed,
+            &self.vault,
+            &self.system_program,
+            &self.recipient_account,
+            token_accounts,
+            amount,
+        )
+    }
+
+    /// Sends a fee from the escrow vault to the marketplace fee vault (or its token account)
+    fn collect_fee(&self, transaction_seed: u64, amount: u64) -> Result<()> {
+        let token_accounts = EscrowTokenAccounts::from_optional(
+            &self.mint,
+            &self.vault_token_account,
+            &self.fee_token_account,
+            &self.token_program,
+            &self.memo_program,
+        );
+        pay_from_escrow(
+            &self.escrow_state,
+            transaction_seed,
+            &self.vault,
+            &self.system_program,
+            &self.fee_vault.to_account_info(),
+            token_accounts,
+            amount,
+        )
+    }
+}
+
+// --- Account Structs ---
+
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    pub new_authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = MarketplaceConfig::LEN,
+        seeds = [b"config".as_ref()],
+        bump
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::SolanaEscrowMarketplace>,
+
+    #[account(constraint = program_data.upgrade_authority_address == Some(payer.key()) @ EscrowError::Unauthorized)]
+    pub program_data: Account<'info, ProgramData>,
+
+    /// Data-less vault accumulating marketplace fees until distribution
+    #[account(
+        mut,
+        seeds = [b"fee_vault".as_ref()],
+        bump
+    )]
+    pub fee_vault: SystemAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(epoch: u64, merkle_root: [u8; 32], total_amount: u64, max_claimants: u32)]
+pub struct PostAirdropEpoch<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"fee_vault".as_ref()],
+        bump = config.fee_vault_bump,
+    )]
+    pub fee_vault: SystemAccount<'info>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = AirdropEpoch::space(max_claimants),
+        seeds = [b"airdrop".as_ref(), epoch.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub airdrop_epoch: Account<'info, AirdropEpoch>,
+
+    /// Data-less vault holding the epoch's unclaimed lamports
+    #[account(
+        mut,
+        seeds = [b"airdrop_vault".as_ref(), airdrop_epoch.key().as_ref()],
+        bump
+    )]
+    pub airdrop_vault: SystemAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(epoch: u64)]
+pub struct ClaimAirdrop<'info> {
+    #[account(mut)]
+    pub claimant: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"airdrop".as_ref(), epoch.to_le_bytes().as_ref()],
+        bump = airdrop_epoch.bump,
+    )]
+    pub airdrop_epoch: Account<'info, AirdropEpoch>,
+
+    #[account(
+        mut,
+        seeds = [b"airdrop_vault".as_ref(), airdrop_epoch.key().as_ref()],
+        bump = airdrop_epoch.vault_bump,
+    )]
+    pub airdrop_vault: SystemAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(epoch: u64)]
+pub struct SweepAirdrop<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"fee_vault".as_ref()],
+        bump = config.fee_vault_bump,
+    )]
+    pub fee_vault: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"airdrop".as_ref(), epoch.to_le_bytes().as_ref()],
+        bump = airdrop_epoch.bump,
+    )]
+    pub airdrop_epoch: Account<'info, AirdropEpoch>,
+
+    #[account(
+        mut,
+        seeds = [b"airdrop_vault".as_ref(), airdrop_epoch.key().as_ref()],
+        bump = airdrop_epoch.vault_bump,
+    )]
+    pub airdrop_vault: SystemAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct BurnTokens<'info> {
+    pub burner: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(mut)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = burner,
+        token::token_program = token_program,
+    )]
+    pub burner_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct DistributeFees<'info> {
+    pub caller: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"fee_vault".as_ref()],
+        bump = config.fee_vault_bump,
+    )]
+    pub fee_vault: SystemAccount<'info>,
+
+    /// CHECK: Burn share destination - address checked against config
+    #[account(mut, address = config.burn_wallet @ EscrowError::IncorrectFeeWallet)]
+    pub burn_wallet: AccountInfo<'info>,
+
+    /// CHECK: Airdrop share destination - address checked against config
+    #[account(mut, address = config.airdrop_wallet @ EscrowError::IncorrectFeeWallet)]
+    pub airdrop_wallet: AccountInfo<'info>,
+
+    /// CHECK: Operations share destination - address checked against config
+    #[account(mut, address = config.fee_wallet @ EscrowError::IncorrectFeeWallet)]
+    pub operations_wallet: AccountInfo<'info>,
+
+    /// CHECK: Development share destination - address checked against config
+    #[account(mut, address = config.development_wallet @ EscrowError::IncorrectFeeWallet)]
+    pub development_wallet: AccountInfo<'info>,
+
+    // --- Token fees only ---
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(mut)]
+    pub fee_vault_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub burn_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub airdrop_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub operations_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub development_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+
+    /// Only needed when a destination token account requires incoming transfer memos
+    pub memo_program: Option<Program<'info, Memo>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetFeeTiers<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = FeeSchedule::LEN,
+        seeds = [b"fee_schedule".as_ref()],
+        bump
+    )]
+    pub fee_schedule: Account<'info, FeeSchedule>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(seller: Pubkey)]
+pub struct SetSellerFeeOverride<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = SellerFeeOverride::LEN,
+        seeds = [b"seller_fee".as_ref(), seller.as_ref()],
+        bump
+    )]
+    pub seller_fee_override: Account<'info, SellerFeeOverride>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(seller: Pubkey)]
+pub struct RemoveSellerFeeOverride<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"seller_fee".as_ref(), seller.as_ref()],
+        bump = seller_fee_override.bump,
+    )]
+    pub seller_fee_override: Account<'info, SellerFeeOverride>,
+}
+
+#[derive(Accounts)]
+#[instruction(transaction_seed: u64)]
+pub struct InitializeEscrow<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    /// CHECK: Seller's account - validated in instruction
+    pub seller: AccountInfo<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(
+        init,
+        payer = buyer,
+        space = EscrowState::LEN,
+        seeds = [b"escrow".as_ref(), buyer.key().as_ref(), transaction_seed.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub escrow_state: Account<'info, EscrowState>,
+
+    /// Data-less vault holding the escrowed lamports
+    #[account(
+        mut,
+        seeds = [b"vault".as_ref(), escrow_state.key().as_ref()],
+        bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Buyer's $SZ token account - only needed to claim the holder discount
+    pub holder_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: Amount tiers PDA - applied whenever the authority has created it
+    #[account(
+        seeds = [b"fee_schedule".as_ref()],
+        bump,
+    )]
+    pub fee_schedule: UncheckedAccount<'info>,
+
+    /// CHECK: Seller's fee override PDA - applied whenever one exists for the seller
+    #[account(
+        seeds = [b"seller_fee".as_ref(), seller.key().as_ref()],
+        bump,
+    )]
+    pub seller_fee_override: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(transaction_seed: u64)]
+pub struct InitializeTokenEscrow<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    /// CHECK: Seller's account - validated in instruction
+    pub seller: AccountInfo<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(
+        init,
+        payer = buyer,
+        space = EscrowState::LEN,
+        seeds = [b"escrow".as_ref(), buyer.key().as_ref(), transaction_seed.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub escrow_state: Account<'info, EscrowState>,
+
+    /// Unused lamport vault - derived so the escrow shares one layout with SOL escrows
+    #[account(
+        seeds = [b"vault".as_ref(), escrow_state.key().as_ref()],
+        bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(mint::token_program = token_program)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = buyer,
+        token::token_program = token_program,
+    )]
+    pub buyer_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Escrow PDA's associated token account holding the escrowed tokens
+    #[account(
+        init,
+        payer = buyer,
+        associated_token::mint = mint,
+        associated_token::authority = escrow_state,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    /// Buyer's $SZ token account - only needed to claim the holder discount
+    pub holder_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: Seller's fee override PDA - applied whenever one exists for the seller
+    #[account(
+        seeds = [b"seller_fee".as_ref(), seller.key().as_ref()],
+        bump,
+    )]
+    pub seller_fee_override: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(listing_id: u64)]
+pub struct CreateListing<'info> {
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(
+        init,
+        payer = seller,
+        space = Listing::LEN,
+        seeds = [b"listing".as_ref(), seller.key().as_ref(), listing_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub listing: Account<'info, Listing>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ManageListing<'info> {
+    pub seller: Signer<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"listing".as_ref(), listing.seller.as_ref(), listing.listing_id.to_le_bytes().as_ref()],
+        bump = listing.bump,
+    )]
+    pub listing: Account<'info, Listing>,
+}
+
+#[derive(Accounts)]
+pub struct CloseListing<'info> {
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    #[account(
+        mut,
+        close = seller,
+        seeds = [b"listing".as_ref(), listing.seller.as_ref(), listing.listing_id.to_le_bytes().as_ref()],
+        bump = listing.bump,
+    )]
+    pub listing: Account<'info, Listing>,
+}
+
+#[derive(Accounts)]
+pub struct PurchaseListing<'info> {
+    pub escrow: InitializeEscrow<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"listing".as_ref(), listing.seller.as_ref(), listing.listing_id.to_le_bytes().as_ref()],
+        bump = listing.bump,
+    )]
+    pub listing: Account<'info, Listing>,
+}
+
+#[derive(Accounts)]
+pub struct PurchaseTokenListing<'info> {
+    pub escrow: InitializeTokenEscrow<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"listing".as_ref(), listing.seller.as_ref(), listing.listing_id.to_le_bytes().as_ref()],
+        bump = listing.bump,
+    )]
+    pub listing: Account<'info, Listing>,
+}
+
+#[derive(Accounts)]
+#[instruction(transaction_seed: u64)]
+pub struct RespondToOffer<'info> {
+    /// Seller to accept, reject or counter; buyer to withdraw or accept a counter-offer
+    #[account(mut)]
+    pub caller: Signer<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"escrow".as_ref(), escrow_state.buyer.as_ref(), transaction_seed.to_le_bytes().as_ref()],
+        bump = escrow_state.bump,
+    )]
+    pub escrow_state: Account<'info, EscrowState>,
+
+    #[account(
+        mut,
+        seeds = [b"vault".as_ref(), escrow_state.key().as_ref()],
+        bump = escrow_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Receives refunds - validated in instruction logic
+    #[account(mut)]
+    pub buyer: SystemAccount<'info>,
+
+    /// Receives the buyer fee on acceptance
+    #[account(
+        mut,
+        seeds = [b"fee_vault".as_ref()],
+        bump = config.fee_vault_bump,
+    )]
+    pub fee_vault: SystemAccount<'info>,
+
+    /// CHECK: Amount tiers PDA - applied to a counter amount whenever the authority has created it
+    #[account(
+        seeds = [b"fee_schedule".as_ref()],
+        bump,
+    )]
+    pub fee_schedule: UncheckedAccount<'info>,
+
+    /// CHECK: Seller's fee override PDA - applied to a counter amount whenever one exists
+    #[account(
+        seeds = [b"seller_fee".as_ref(), escrow_state.seller.as_ref()],
+        bump,
+    )]
+    pub seller_fee_override: UncheckedAccount<'info>,
+
+    /// Buyer's $SZ token account - only needed to claim the holder discount on a counter-offer
+    pub holder_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(transaction_seed: u64)]
+pub struct MarkShipped<'info> {
+    pub seller: Signer<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"escrow".as_ref(), escrow_state.buyer.as_ref(), transaction_seed.to_le_bytes().as_ref()],
+        bump = escrow_state.bump,
+    )]
+    pub escrow_state: Account<'info, EscrowState>,
+}
+
+#[derive(Accounts)]
+#[instruction(transaction_seed: u64)]
+pub struct OpenDispute<'info> {
+    #[account(mut)]
+    pub party: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"escrow".as_ref(), escrow_state.buyer.as_ref(), transaction_seed.to_le_bytes().as_ref()],
+        bump = escrow_state.bump,
+    )]
+    pub escrow_state: Account<'info, EscrowState>,
+
+    #[account(
+        init,
+        payer = party,
+        space = Dispute::LEN,
+        seeds = [b"dispute".as_ref(), escrow_state.key().as_ref()],
+        bump
+    )]
+    pub dispute: Account<'info, Dispute>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(transaction_seed: u64)]
+pub struct SubmitEvidence<'info> {
+    pub party: Signer<'info>,
+
+    #[account(
+        seeds = [b"escrow".as_ref(), escrow_state.buyer.as_ref(), transaction_seed.to_le_bytes().as_ref()],
+        bump = escrow_state.bump,
+    )]
+    pub escrow_state: Account<'info, EscrowState>,
+
+    #[account(
+        mut,
+        seeds = [b"dispute".as_ref(), escrow_state.key().as_ref()],
+        bump = dispute.bump,
+    )]
+    pub dispute: Account<'info, Dispute>,
+}
+
+#[derive(Accounts)]
+#[instruction(transaction_seed: u64)]
+pub struct ResolveDispute<'info> {
+    pub settlement: ProcessEscrow<'info>,
+
+    #[account(
+        mut,
+        close = dispute_opener,
+        seeds = [b"dispute".as_ref(), settlement.escrow_state.key().as_ref()],
+        bump = dispute.bump,
+    )]
+    pub dispute: Account<'info, Dispute>,
+
+    /// CHECK: Receives the dispute account rent - must be whoever opened the dispute
+    #[account(mut, address = dispute.opened_by @ EscrowError::NotEscrowParty)]
+    pub dispute_opener: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(transaction_seed: u64)]
+pub struct SettleSplit<'info> {
+    #[account(mut)]
+    pub caller: Signer<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"escrow".as_ref(), escrow_state.buyer.as_ref(), transaction_seed.to_le_bytes().as_ref()],
+        bump = escrow_state.bump,
+    )]
+    pub escrow_state: Account<'info, EscrowState>,
+
+    #[account(
+        mut,
+        seeds = [b"vault".as_ref(), escrow_state.key().as_ref()],
+        bump = escrow_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// CHECK: Seller's account - validated in instruction logic
+    #[account(mut)]
+    pub seller: AccountInfo<'info>,
+
+    /// CHECK: Buyer's account - validated in instruction logic
+    #[account(mut)]
+    pub buyer: AccountInfo<'info>,
+
+    /// Receives the seller fee on the seller's share
+    #[account(
+        mut,
+        seeds = [b"fee_vault".as_ref()],
+        bump = config.fee_vault_bump,
+    )]
+    pub fee_vault: SystemAccount<'info>,
+
+    // --- Disputed escrows only ---
+    #[account(
+        mut,
+        seeds = [b"dispute".as_ref(), escrow_state.key().as_ref()],
+        bump = dispute.bump,
+    )]
+    pub dispute: Option<Account<'info, Dispute>>,
+
+    /// CHECK: Receives the dispute account rent - validated in instruction logic
+    #[account(mut)]
+    pub dispute_opener: Option<AccountInfo<'info>>,
+
+    // --- Token escrows only ---
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(mut)]
+    pub vault_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub seller_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub buyer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub fee_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+
+    /// Only needed when a recipient token account requires incoming transfer memos
+    pub memo_program: Option<Program<'info, Memo>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(transaction_seed: u64)]
+pub struct ProcessEscrow<'info> {
+    #[account(mut)]
+    pub caller: Signer<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"escrow".as_ref(), escrow_state.buyer.as_ref(), transaction_seed.to_le_bytes().as_ref()],
+        bump = escrow_state.bump,
+    )]
+    pub escrow_state: Account<'info, EscrowState>,
+
+    #[account(
+        mut,
+        seeds = [b"vault".as_ref(), escrow_state.key().as_ref()],
+        bump = escrow_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// CHECK: Recipient account - validated in instruction logic
+    #[account(mut)]
+    pub recipient_account: AccountInfo<'info>,
+
+    /// Receives the seller fee on release
+    #[account(
+        mut,
+        seeds = [b"fee_vault".as_ref()],
+        bump = config.fee_vault_bump,
+    )]
+    pub fee_vault: SystemAccount<'info>,
+
+    // --- Token escrows only ---
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(mut)]
+    pub vault_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub recipient_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Fee vault's token account - receives the seller fee on release
+    #[account(mut)]
+    pub fee_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+
+    /// Only needed when the recipient token account requires incoming transfer memos
+    pub memo_program: Option<Program<'info, Memo>>,
+
+    /// CHECK: Listing the order was bought from - required to restock it when an unaccepted
+    /// purchase is refunded; matched against the escrow in instruction logic
+    #[account(mut)]
+    pub listing: Option<UncheckedAccount<'info>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(transaction_seed: u64)]
+pub struct CloseEscrow<'info> {
+    #[account(mut)]
+    pub caller: Signer<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(
+        mut,
+        close = buyer,
+        seeds = [b"escrow".as_ref(), escrow_state.buyer.as_ref(), transaction_seed.to_le_bytes().as_ref()],
+        bump = escrow_state.bump,
+    )]
+    pub escrow_state: Account<'info, EscrowState>,
+
+    #[account(
+        mut,
+        seeds = [b"vault".as_ref(), escrow_state.key().as_ref()],
+        bump = escrow_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// CHECK: Original rent payer - must match the escrow buyer
+    #[account(mut, address = escrow_state.buyer @ EscrowError::RecipientNotBuyer)]
+    pub buyer: AccountInfo<'info>,
+
+    // --- Token escrows only ---
+    /// Only needed when the vault holds withheld transfer fees, which are harvested to it
+    #[account(mut)]
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(mut)]
+    pub vault_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(transaction_seed: u64)]
+pub struct MigrateEscrow<'info> {
+    #[account(mut)]
+    pub caller: Signer<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    /// CHECK: Escrow written by the original program at the seed-only address. It predates
+    /// the current `EscrowState` layout, so it is decoded as `LegacyEscrowState` in the handler.
+    #[account(
+        mut,
+        seeds = [b"escrow".as_ref(), transaction_seed.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub legacy_escrow: UncheckedAccount<'info>,
+
+    /// CHECK: Must be the legacy escrow's buyer - checked in the handler
+    pub buyer: UncheckedAccount<'info>,
+
+    #[account(
+        init,
+        payer = caller,
+        space = EscrowState::LEN,
+        seeds = [b"escrow".as_ref(), buyer.key().as_ref(), transaction_seed.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub escrow_state: Account<'info, EscrowState>,
+
+    #[account(
+        mut,
+        seeds = [b"vault".as_ref(), escrow_state.key().as_ref()],
+        bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+// --- State Accounts ---
+
+#[account]
+pub struct MarketplaceConfig {
+    pub authority: Pubkey,              // 32 bytes - releases and cancels escrows
+    pub fee_wallet: Pubkey,             // 32 bytes - receives the operations share of distributed fees
+    pub max_fee_basis_points: u16,      // 2 bytes
+    pub min_escrow_amount: u64,         // 8 bytes - lamports
+    pub min_net_amount: u64,            // 8 bytes - lamports left for seller after fee
+    pub escrow_retention_period: i64,   // 8 bytes - seconds a settled escrow is kept before close
+    pub pending_authority: Option<Pubkey>, // 33 bytes - awaiting accept_authority
+    pub reject_permanent_delegate: bool, // 1 byte - refuse Token-2022 mints with a permanent delegate
+    pub delivery_period: i64,           // 8 bytes - seconds the seller has to ship
+    pub confirmation_period: i64,       // 8 bytes - seconds the buyer has to confirm after delivery
+    pub arbiter: Pubkey,                // 32 bytes - resolves disputes
+    pub buyer_fee_basis_points: u16,    // 2 bytes - charged on top of the buyer's deposit
+    pub seller_fee_basis_points: u16,   // 2 bytes - deducted from the seller's payout
+    pub discount_mint: Option<Pubkey>,  // 33 bytes - holder discount mint, None disables the discount
+    pub discount_threshold: u64,        // 8 bytes - minimum holder balance, in base units
+    pub holder_discount_basis_points: u16, // 2 bytes - share of the buyer fee waived for holders
+    pub burn_wallet: Pubkey,            // 32 bytes - receives the buyback-and-burn share
+    pub airdrop_wallet: Pubkey,         // 32 bytes - receives the holder airdrop share
+    pub development_wallet: Pubkey,     // 32 bytes - receives the development share
+    pub fee_distribution: FeeDistribution, // 8 bytes - revenue split ratios
+    pub fee_vault_bump: u8,             // 1 byte
+    pub total_burned: u64,              // 8 bytes - cumulative $SZ burned, in base units
+    pub paused_instructions: u16,       // 2 bytes - mask of PAUSE_* flags
+    pub sz_mint: Option<Pubkey>,        // 33 bytes - $SZ mint accepted by burn_tokens, None disables burns
+    pub airdrop_drawn: u64,             // 8 bytes - lamports airdrop epochs took from the fee vault since the last distribution
+    pub bump: u8,                       // 1 byte
+}
+
+impl MarketplaceConfig {
+    // 8 (discriminator) + 32*3 (pubkeys) + 2 (u16) + 8*2 (u64s) + 8*3 (i64s) + 33 (option pubkey) + 1 (bool) + 1 (u8)
+    // + 2*2 (fee rates) + 33 (discount mint) + 8 (threshold) + 2 (discount)
+    // + 32*3 (distribution wallets) + 8 (distribution) + 1 (fee vault bump) + 8 (burned counter)
+    // + 2 (pause flags) + 33 ($SZ mint) + 8 (airdrop drawn)
+    const LEN: usize = 8 + (32 * 3) + 2 + (8 * 2) + (8 * 3) + 33 + 1 + 1 + (2 * 2) + 33 + 8 + 2
+        + (32 * 3) + FeeDistribution::LEN + 1 + 8 + 2 + 33 + 8;
+
+    /// Fails if any of the `flags` instruction groups is paused
+    fn check_not_paused(&self, flags: u16) -> Result<()> {
+        require!(self.paused_instructions & flags == 0, EscrowError::InstructionPaused);
+        Ok(())
+    }
+
+    /// Resolves the fee rates for a new escrow: config rates, lowered by the amount tier `amount`
+    /// reaches (SOL escrows only) and by the seller's override, then the $SZ holder discount on
+    /// the buyer fee. Tiers and overrides lower the seller's rate too, so instructions take both
+    /// accounts at their fixed addresses and apply them whenever they exist.
+    fn fee_rates(
+        &self,
+        amount: Option<u64>,
+        fee_schedule: Option<&FeeSchedule>,
+        seller_fee_override: Option<&SellerFeeOverride>,
+        buyer: Pubkey,
+        holder_token_account: Option<&InterfaceAccount<TokenAccount>>,
+    ) -> FeeRates {
+        let mut rates = FeeRates {
+            buyer_fee_basis_points: self.buyer_fee_basis_points,
+            seller_fee_basis_points: self.seller_fee_basis_points,
+            fee_tier: None,
+            fee_override_applied: false,
+            fee_discount_applied: false,
+        };
+
+        // --- Amount Tier ---
+        if let (Some(amount), Some(fee_schedule)) = (amount, fee_schedule) {
+            if let Some(index) = fee_schedule.tiers.iter().rposition(|tier| amount >= tier.min_amount) {
+                let tier = &fee_schedule.tiers[index];
+                rates.buyer_fee_basis_points = rates.buyer_fee_basis_points.min(tier.buyer_fee_basis_points);
+                rates.seller_fee_basis_points = rates.seller_fee_basis_points.min(tier.seller_fee_basis_points);
+                rates.fee_tier = Some(index as u8);
+            }
+        }
+
+        // --- Seller Override ---
+        if let Some(seller_fee_override) = seller_fee_override {
+            rates.buyer_fee_basis_points = rates.buyer_fee_basis_points.min(seller_fee_override.buyer_fee_basis_points);
+            rates.seller_fee_basis_points = rates.seller_fee_basis_points.min(seller_fee_override.seller_fee_basis_points);
+            rates.fee_override_applied = true;
+        }
+
+        // --- Holder Discount ---
+        // The holder account must belong to the buyer, be of the discount mint and hold the threshold
+        rates.fee_discount_applied = match (self.discount_mint, holder_token_account) {
+            (Some(discount_mint), Some(holder)) => {
+                holder.owner == buyer && holder.mint == discount_mint && holder.amount >= self.discount_threshold
+            }
+            _ => false,
+        };
+        if rates.fee_discount_applied {
+            // Both factors are at most 10000, so this can't overflow u32
+            let waived = (rates.buyer_fee_basis_points as u32 * self.holder_discount_basis_points as u32 / 10000) as u16;
+            rates.buyer_fee_basis_points -= waived;
+        }
+        rates
+    }
+}
+
+#[account]
+pub struct AirdropEpoch {
+    pub epoch: u64,                     // 8 bytes
+    pub merkle_root: [u8; 32],          // 32 bytes - root over (index, claimant, amount) leaves
+    pub total_amount: u64,              // 8 bytes - lamports funded from the fee vault
+    pub claimed_amount: u64,            // 8 bytes
+    pub max_claimants: u32,             // 4 bytes
+    pub created_at: i64,                // 8 bytes
+    pub expires_at: i64,                // 8 bytes - unclaimed funds can be swept after this
+    pub bump: u8,                       // 1 byte
+    pub vault_bump: u8,                 // 1 byte
+    pub claimed_bitmap: Vec<u8>,        // 4 + ceil(max_claimants / 8) bytes - one bit per leaf index
+}
+
+impl AirdropEpoch {
+    // 8 (discriminator) + 8 (u64) + 32 (root) + 8*2 (amounts) + 4 (u32) + 8*2 (timestamps) + 1 + 1 (bumps)
+    // + 4 + bitmap
+    fn space(max_claimants: u32) -> usize {
+        8 + 8 + 32 + (8 * 2) + 4 + (8 * 2) + 1 + 1 + 4 + Self::bitmap_len(max_claimants)
+    }
+
+    fn bitmap_len(max_claimants: u32) -> usize {
+        (max_claimants as usize).div_ceil(8)
+    }
+
+    fn is_claimed(&self, index: u32) -> bool {
+        self.claimed_bitmap[index as usize / 8] & (1 << (index % 8)) != 0
+    }
+
+    fn set_claimed(&mut self, index: u32) {
+        self.claimed_bitmap[index as usize / 8] |= 1 << (index % 8);
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FeeDistribution {
+    pub burn_basis_points: u16,         // 2 bytes
+    pub airdrop_basis_points: u16,      // 2 bytes
+    pub operations_basis_points: u16,   // 2 bytes
+    pub development_basis_points: u16,  // 2 bytes
+}
+
+impl FeeDistribution {
+    const LEN: usize = 2 * 4;
+
+    fn validate(&self) -> Result<()> {
+        let total = self.burn_basis_points as u32
+            + self.airdrop_basis_points as u32
+            + self.operations_basis_points as u32
+            + self.development_basis_points as u32;
+        require!(total == 10000, EscrowError::InvalidFeeDistribution);
+        Ok(())
+    }
+
+    /// Splits `total` into burn, airdrop, operations and development shares.
+    /// Operations takes the rounding remainder so the shares always add up to `total`.
+    fn split(&self, total: u64) -> Result<[u64; 4]> {
+        let share = |basis_points: u16| {
+            (total as u128)
+                .checked_mul(basis_points as u128)
+                .map(|x| (x / 10000) as u64)
+                .ok_or(EscrowError::ArithmeticOverflow)
+        };
+        let burn = share(self.burn_basis_points)?;
+        let airdrop = share(self.airdrop_basis_points)?;
+        let development = share(self.development_basis_points)?;
+        let operations = total
+            .checked_sub(burn)
+            .and_then(|x| x.checked_sub(airdrop))
+            .and_then(|x| x.checked_sub(development))
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        Ok([burn, airdrop, operations, development])
+    }
+}
+
+#[account]
+pub struct FeeSchedule {
+    pub tiers: Vec<FeeTier>,            // 4 + 12 * MAX_FEE_TIERS bytes - ascending by min_amount
+    pub bump: u8,                       // 1 byte
+}
+
+impl FeeSchedule {
+    // 8 (discriminator) + 4 + 12*MAX_FEE_TIERS (tiers) + 1 (u8)
+    const LEN: usize = 8 + 4 + (FeeTier::LEN * MAX_FEE_TIERS) + 1;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Debug)]
+pub struct FeeTier {
+    pub min_amount: u64,                // 8 bytes - lamports the escrow must reach
+    pub buyer_fee_basis_points: u16,    // 2 bytes
+    pub seller_fee_basis_points: u16,   // 2 bytes
+}
+
+impl FeeTier {
+    const LEN: usize = 8 + 2 + 2;
+}
+
+#[account]
+pub struct SellerFeeOverride {
+    pub seller: Pubkey,                 // 32 bytes
+    pub buyer_fee_basis_points: u16,    // 2 bytes
+    pub seller_fee_basis_points: u16,   // 2 bytes
+    pub bump: u8,                       // 1 byte
+}
+
+impl SellerFeeOverride {
+    // 8 (discriminator) + 32 (pubkey) + 2*2 (fee rates) + 1 (u8)
+    const LEN: usize = 8 + 32 + (2 * 2) + 1;
+}
+
+#[account]
+pub struct Listing {
+    pub seller: Pubkey,                 // 32 bytes
+    pub listing_id: u64,                // 8 bytes - seller-chosen id, part of the PDA seeds
+    pub price: u64,                     // 8 bytes - per unit, in lamports or token base units
+    pub mint: Option<Pubkey>,           // 33 bytes - currency, None for native SOL
+    pub quantity: u32,                  // 4 bytes - units left to sell
+    pub status: ListingStatus,          // 1 byte
+    pub created_at: i64,                // 8 bytes - timestamp
+    pub bump: u8,                       // 1 byte
+}
+
+impl Listing {
+    // 8 (discriminator) + 32 (pubkey) + 8*2 (id, price) + 33 (option pubkey) + 4 (u32) + 1 (enum) + 8 (timestamp) + 1 (u8)
+    const LEN: usize = 8 + 32 + (8 * 2) + 33 + 4 + 1 + 8 + 1;
+
+    /// Checks the purchase matches this listing's seller and currency, takes `quantity` units out of
+    /// inventory and returns the total price to escrow
+    fn reserve(&mut self, seller: Pubkey, mint: Option<Pubkey>, quantity: u32) -> Result<u64> {
+        require_keys_eq!(seller, self.seller, EscrowError::RecipientNotSeller);
+        require!(mint == self.mint, EscrowError::MintMismatch);
+        require!(quantity > 0, EscrowError::ZeroAmount);
+        require!(self.status != ListingStatus::Paused, EscrowError::ListingPaused);
+        require!(self.status != ListingStatus::SoldOut, EscrowError::ListingSoldOut);
+        require!(quantity <= self.quantity, EscrowError::InsufficientListingQuantity);
+
+        let total_price = self.price
+            .checked_mul(quantity as u64)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        self.quantity -= quantity;
+        if self.quantity == 0 {
+            self.status = ListingStatus::SoldOut;
+        }
+        Ok(total_price)
+    }
+
+    /// Puts back `quantity` units reserved by a refunded, unaccepted purchase.
+    /// A listing the seller has since closed is left closed.
+    fn restock_from_refund(account: &AccountInfo, quantity: u32) -> Result<()> {
+        let Some(mut listing) = load_if_initialized::<Listing>(account)? else {
+            return Ok(());
+        };
+        listing.quantity = listing.quantity
+            .checked_add(quantity)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        if listing.status == ListingStatus::SoldOut {
+            listing.status = ListingStatus::Active;
+        }
+        listing.try_serialize(&mut &mut account.try_borrow_mut_data()?[..])?;
+
+        emit!(ListingUpdated {
+            listing: account.key(),
+            price: listing.price,
+            quantity: listing.quantity,
+            status: listing.status,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+}
+
+#[account]
+pub struct EscrowState {
+    pub buyer: Pubkey,                  // 32 bytes
+    pub seller: Pubkey,                 // 32 bytes  
+    pub marketplace_authority: Pubkey,  // 32 bytes
+    pub total_initial_amount: u64,      // 8 bytes
+    pub buyer_fee_amount: u64,          // 8 bytes - paid by the buyer on top at funding
+    pub amount_for_seller: u64,         // 8 bytes - held in the vault, before seller fee
+    pub mint: Option<Pubkey>,           // 33 bytes - None for native SOL escrows
+    pub stage: EscrowStage,             // 1 byte
+    pub is_initialized: bool,           // 1 byte
+    pub bump: u8,                       // 1 byte
+    pub vault_bump: u8,                 // 1 byte
+    pub created_at: i64,                // 8 bytes - timestamp
+    pub completed_at: i64,              // 8 bytes - completion timestamp
+    pub shipped_at: i64,                // 8 bytes - 0 until the seller marks shipped
+    pub delivery_deadline: i64,         // 8 bytes - refundable if not shipped by then
+    pub confirmation_deadline: i64,     // 8 bytes - auto-releasable after this once shipped, reset from shipped_at
+    pub seller_settled_amount: u64,     // 8 bytes - paid to seller on settlement
+    pub buyer_refunded_amount: u64,     // 8 bytes - refunded to buyer on settlement
+    pub seller_fee_basis_points: u16,   // 2 bytes - seller fee rate at funding
+    pub seller_fee_amount: u64,         // 8 bytes - deducted at release; pro rata on split, 0 on refund
+    pub fee_discount_applied: bool,     // 1 byte - buyer fee had the $SZ holder discount
+    pub buyer_fee_basis_points: u16,    // 2 bytes - buyer fee rate charged, after any discount
+    pub fee_tier: Option<u8>,           // 2 bytes - fee schedule tier index, None for base rates
+    pub fee_override_applied: bool,     // 1 byte - seller fee override was applied
+    pub order_reference: [u8; 32],      // 32 bytes - hash of the listing id and order metadata
+    pub counter_amount: u64,            // 8 bytes - seller's counter-offer, 0 when none is pending
+    pub tracking_hash: [u8; 32],        // 32 bytes - commitment to the shipment tracking number
+    pub carrier_code: u16,              // 2 bytes - marketplace-defined shipping carrier id
+    pub listing: Option<Pubkey>,        // 33 bytes - listing the order was bought from, if any
+    pub listing_quantity: u32,          // 4 bytes - units reserved from that listing
+}
+
+impl EscrowState {
+    // 8 (discriminator) + 32*3 (pubkeys) + 8*5 (u64s) + 33 (option pubkey) + 1 (enum) + 1 (bool) + 1 + 1 (bumps) + 8*5 (timestamps)
+    // + 8*2 (settled amounts) + 2 (seller fee rate) + 8 (seller fee) + 1 (discount flag) + 2 (buyer fee rate)
+    // + 2 (fee tier) + 1 (override flag) + 32 (order reference) + 8 (counter amount) + 32 (tracking hash) + 2 (carrier code)
+    // + 33 (listing) + 4 (listing quantity)
+    const LEN: usize = 8 + (32 * 3) + (8 * 5) + 33 + 1 + 1 + 1 + 1 + (8 * 5) + (8 * 2) + 2 + 8 + 1 + 2 + 2 + 1 + 32 + 8 + 32 + 2
+        + 33 + 4;
+
+    /// Fixes the delivery and confirmation deadlines from `started_at` and the config windows.
+    /// `mark_shipped` later restarts the confirmation window from the shipped time.
+    fn set_deadlines(&mut self, config: &MarketplaceConfig, started_at: i64) -> Result<()> {
+        self.delivery_deadline = started_at
+            .checked_add(config.delivery_period)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        self.confirmation_deadline = self.delivery_deadline
+            .checked_add(config.confirmation_period)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        Ok(())
+    }
+}
+
+/// Escrow state as written by the original program, before vaults, mints and deadlines.
+/// It held the escrowed lamports itself and shares `EscrowState`'s discriminator.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct LegacyEscrowState {
+    pub buyer: Pubkey,                  // 32 bytes
+    pub seller: Pubkey,                 // 32 bytes
+    pub marketplace_authority: Pubkey,  // 32 bytes
+    pub total_initial_amount: u64,      // 8 bytes
+    pub fee_amount: u64,                // 8 bytes - taken out of the deposit at funding
+    pub amount_for_seller: u64,         // 8 bytes - held in the escrow account itself
+    pub stage: EscrowStage,             // 1 byte - only Funded, Released or Cancelled
+    pub is_initialized: bool,           // 1 byte
+    pub bump: u8,                       // 1 byte - never set by the original program
+    pub created_at: i64,                // 8 bytes - timestamp
+    pub completed_at: i64,              // 8 bytes - completion timestamp
+}
+
+impl LegacyEscrowState {
+    // Space the original program allocated - it counted 8*5 for the three u64s, leaving 16 trailing bytes unused
+    // 8 (discriminator) + 32*3 (pubkeys) + 8*5 (u64s) + 1 (enum) + 1 (bool) + 1 (u8) + 8*2 (timestamps)
+    const LEN: usize = 8 + (32 * 3) + (8 * 5) + 1 + 1 + 1 + (8 * 2);
+
+    /// Decodes a legacy escrow, rejecting any account this program didn't write in that layout.
+    fn try_from_account(account: &AccountInfo) -> Result<Self> {
+        require_keys_eq!(*account.owner, crate::ID, EscrowError::InvalidLegacyEscrow);
+        let data = account.try_borrow_data()?;
+        require!(
+            data.len() == Self::LEN && &data[..8] == EscrowState::DISCRIMINATOR,
+            EscrowError::InvalidLegacyEscrow
+        );
+        Self::deserialize(&mut &data[8..]).map_err(|_| error!(EscrowError::InvalidLegacyEscrow))
+    }
+}
+
+#[account]
+pub struct Dispute {
+    pub escrow: Pubkey,                 // 32 bytes
+    pub opened_by: Pubkey,              // 32 bytes - buyer or seller, refunded the rent on resolution
+    pub opened_at: i64,                 // 8 bytes
+    pub evidence: Vec<DisputeEvidence>, // 4 + 72 * 2 * MAX_DISPUTE_EVIDENCE_PER_PARTY bytes
+    pub bump: u8,                       // 1 byte
+}
+
+impl Dispute {
+    // 8 (discriminator) + 32*2 (pubkeys) + 8 (i64) + 4 (vec len) + 72*2*MAX_DISPUTE_EVIDENCE_PER_PARTY (evidence, per party) + 1 (u8)
+    const LEN: usize = 8 + (32 * 2) + 8 + 4 + (DisputeEvidence::LEN * 2 * MAX_DISPUTE_EVIDENCE_PER_PARTY) + 1;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Debug)]
+pub struct DisputeEvidence {
+    pub submitter: Pubkey,              // 32 bytes
+    pub content_hash: [u8; 32],         // 32 bytes - SHA-256 / IPFS digest of the evidence
+    pub submitted_at: i64,              // 8 bytes
+}
+
+impl DisputeEvidence {
+    const LEN: usize = 32 + 32 + 8;
+}
+
+// --- Events ---
+
+#[event]
+pub struct ConfigUpdated {
+    pub authority: Pubkey,
+    pub fee_wallet: Pubkey,
+    pub max_fee_basis_points: u16,
+    pub min_escrow_amount: u64,
+    pub min_net_amount: u64,
+    pub escrow_retention_period: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FeeRatesUpdated {
+    pub buyer_fee_basis_points: u16,
+    pub seller_fee_basis_points: u16,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct HolderDiscountUpdated {
+    pub discount_mint: Option<Pubkey>,
+    pub discount_threshold: u64,
+    pub holder_discount_basis_points: u16,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FeeTiersUpdated {
+    pub tiers: Vec<FeeTier>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SellerFeeOverrideSet {
+    pub seller: Pubkey,
+    pub buyer_fee_basis_points: u16,
+    pub seller_fee_basis_points: u16,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SellerFeeOverrideRemoved {
+    pub seller: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FeeDistributionUpdated {
+    pub burn_wallet: Pubkey,
+    pub airdrop_wallet: Pubkey,
+    pub operations_wallet: Pubkey,
+    pub development_wallet: Pubkey,
+    pub fee_distribution: FeeDistribution,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FeesDistributed {
+    pub mint: Option<Pubkey>,
+    pub total: u64,
+    pub burn_amount: u64,
+    pub airdrop_amount: u64,
+    pub operations_amount: u64,
+    pub development_amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SzMintUpdated {
+    pub sz_mint: Option<Pubkey>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TokensBurned {
+    pub burner: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub total_burned: u64,
+    pub burn_target: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AirdropEpochPosted {
+    pub epoch: u64,
+    pub merkle_root: [u8; 32],
+    pub total_amount: u64,
+    pub max_claimants: u32,
+    pub expires_at: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AirdropClaimed {
+    pub epoch: u64,
+    pub index: u32,
+    pub claimant: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AirdropSwept {
+    pub epoch: u64,
+    pub total_amount: u64,
+    pub claimed_amount: u64,
+    pub swept_amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PauseUpdated {
+    pub paused_instructions: u16,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TokenPolicyUpdated {
+    pub reject_permanent_delegate: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DeadlinePeriodsUpdated {
+    pub delivery_period: i64,
+    pub confirmation_period: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ArbiterUpdated {
+    pub previous_arbiter: Pubkey,
+    pub arbiter: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AuthorityProposed {
+    pub authority: Pubkey,
+    pub pending_authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AuthorityAccepted {
+    pub previous_authority: Pubkey,
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AuthorityProposalCancelled {
+    pub authority: Pubkey,
+    pub cancelled_authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EscrowCreated {
+    pub escrow_id: Pubkey,
+    pub buyer: Pubkey,
+    pub seller: Pubkey,
+    pub mint: Option<Pubkey>,
+    pub amount: u64,
+    pub buyer_fee: u64,
+    pub seller_fee: u64,
+    pub fee_discount_applied: bool,
+    pub order_reference: [u8; 32],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ListingCreated {
+    pub listing: Pubkey,
+    pub seller: Pubkey,
+    pub listing_id: u64,
+    pub mint: Option<Pubkey>,
+    pub price: u64,
+    pub quantity: u32,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ListingPurchased {
+    pub listing: Pubkey,
+    pub escrow_id: Pubkey,
+    pub buyer: Pubkey,
+    pub quantity: u32,
+    pub total_price: u64,
+    pub remaining_quantity: u32,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ListingUpdated {
+    pub listing: Pubkey,
+    pub price: u64,
+    pub quantity: u32,
+    pub status: ListingStatus,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ListingClosed {
+    pub listing: Pubkey,
+    pub seller: Pubkey,
+    pub listing_id: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OfferCountered {
+    pub escrow_id: Pubkey,
+    pub buyer: Pubkey,
+    pub seller: Pubkey,
+    pub offer_amount: u64,
+    pub counter_amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OfferAccepted {
+    pub escrow_id: Pubkey,
+    pub buyer: Pubkey,
+    pub seller: Pubkey,
+    pub amount: u64,
+    pub buyer_fee: u64,
+    pub seller_fee: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OrderAccepted {
+    pub escrow_id: Pubkey,
+    pub buyer: Pubkey,
+    pub seller: Pubkey,
+    pub buyer_fee: u64,
+    pub delivery_deadline: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OrderShipped {
+    pub escrow_id: Pubkey,
+    pub buyer: Pubkey,
+    pub seller: Pubkey,
+    pub tracking_hash: [u8; 32],
+    pub carrier_code: u16,
+    pub confirmation_deadline: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EscrowMigrated {
+    pub legacy_escrow_id: Pubkey,
+    pub escrow_id: Pubkey,
+    pub buyer: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EscrowCompleted {
+    pub escrow_id: Pubkey,
+    pub buyer: Pubkey,
+    pub seller: Pubkey,
+    pub amount: u64,
+    pub buyer_fee: u64,
+    pub seller_fee: u64,
+    pub action: String,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EscrowSplitSettled {
+    pub escrow_id: Pubkey,
+    pub seller_amount: u64,
+    pub buyer_amount: u64,
+    pub buyer_fee_kept: u64,
+    pub seller_fee: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EscrowArchived {
+    pub escrow_id: Pubkey,
+    pub buyer: Pubkey,
+    pub seller: Pubkey,
+    pub marketplace_authority: Pubkey,
+    pub total_initial_amount: u64,
+    pub buyer_fee_amount: u64,
+    pub seller_fee_amount: u64,
+    pub amount_for_seller: u64,
+    pub seller_settled_amount: u64,
+    pub buyer_refunded_amount: u64,
+    pub stage: EscrowStage,
+    pub created_at: i64,
+    pub completed_at: i64,
+    pub rent_returned: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DisputeOpened {
+    pub escrow_id: Pubkey,
+    pub opened_by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EvidenceSubmitted {
+    pub escrow_id: Pubkey,
+    pub submitter: Pubkey,
+    pub content_hash: [u8; 32],
+    pub index: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DisputeResolved {
+    pub escrow_id: Pubkey,
+    pub arbiter: Pubkey,
+    pub released_to_seller: bool,
+    pub amount: u64,
+    pub evidence_count: u8,
+    pub timestamp: i64,
+}
+
+// --- Enums ---
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Copy, Debug)]
+pub enum EscrowStage {
+    Funded,     // Funds held, awaiting the seller's acceptance
+    Released,   // Funds released to seller
+    Cancelled,  // Funds returned to buyer (minus fee)
+    Disputed,   // Funds frozen until the arbiter resolves
+    Split,      // Funds divided between seller and buyer (minus fee)
+    Offered,    // Buyer's offer held, awaiting the seller; no fee charged yet
+    Accepted,   // Seller accepted the order, delivery deadlines running
+    Shipped,    // Seller marked the order shipped, confirmation window running
+}
+
+impl EscrowStage {
+    /// Whether the seller has accepted, funds are held and the escrow can settle through the normal paths
+    pub fn is_active(&self) -> bool {
+        matches!(self, EscrowStage::Accepted | EscrowStage::Shipped)
+    }
+
+    /// Whether the escrowed funds have been paid out and the escrow can be closed
+    pub fn is_settled(&self) -> bool {
+        matches!(self, EscrowStage::Released | EscrowStage::Cancelled | EscrowStage::Split)
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Copy, Debug)]
+pub enum ListingStatus {
+    Active,     // Open for purchases
+    SoldOut,    // No units left
+    Paused,     // Seller has paused purchases
+}
+
+/// Seller's portion of the held amount in a split settlement
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SplitShare {
+    Amount(u64),      // Absolute amount in lamports or token base units
+    BasisPoints(u16), // Share of the held amount, out of 10000
+}
+
+impl SplitShare {
+    fn seller_amount(&self, held_amount: u64) -> Result<u64> {
+        match *self {
+            SplitShare::Amount(amount) => {
+                require!(amount <= held_amount, EscrowError::InvalidSplitShare);
+                Ok(amount)
+            }
+            SplitShare::BasisPoints(basis_points) => {
+                require!(basis_points <= 10000, EscrowError::InvalidSplitShare);
+                held_amount
+                    .checked_mul(basis_points as u64)
+                    .and_then(|x| x.checked_div(10000))
+                    .ok_or_else(|| error!(EscrowError::ArithmeticOverflow))
+            }
+        }
+    }
+}
+
+/// How an escrow was settled, reported as `EscrowCompleted::action`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CompletionAction {
+    Released,        // Authority released to seller
+    BuyerConfirmed,  // Buyer confirmed receipt
+    AutoReleased,    // Confirmation window lapsed
+    Cancelled,       // Authority refunded buyer
+    AutoRefunded,    // Delivery deadline missed
+    DisputeReleased, // Arbiter ruled for the seller
+    DisputeRefunded, // Arbiter ruled for the buyer
+    Split,           // Authority or arbiter divided the funds
+    OfferRejected,   // Seller rejected the buyer's offer
+    OfferWithdrawn,  // Buyer withdrew an unaccepted offer
+    BuyerCancelled,  // Buyer cancelled before the seller accepted
+    SellerRejected,  // Seller declined before accepting
+}
+
+impl CompletionAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CompletionAction::Released => "released",
+            CompletionAction::BuyerConfirmed => "buyer_confirmed",
+            CompletionAction::AutoReleased => "auto_released",
+            CompletionAction::Cancelled => "cancelled",
+            CompletionAction::AutoRefunded => "auto_refunded",
+            CompletionAction::DisputeReleased => "dispute_released",
+            CompletionAction::DisputeRefunded => "dispute_refunded",
+            CompletionAction::Split => "split",
+            CompletionAction::OfferRejected => "offer_rejected",
+            CompletionAction::OfferWithdrawn => "offer_withdrawn",
+            CompletionAction::BuyerCancelled => "buyer_cancelled",
+            CompletionAction::SellerRejected => "seller_rejected",
+        }
+    }
+
+    /// Dispute rulings settle only disputed escrows, splits settle either, offer refunds settle
+    /// only open offers, buyer cancellations and seller rejections only unaccepted orders, authority cancellations
+    /// unaccepted or active ones, and every other action settles only active ones
+    fn check_stage(&self, stage: EscrowStage) -> Result<()> {
+        match self {
+            CompletionAction::BuyerCancelled | CompletionAction::SellerRejected => {
+                require!(stage != EscrowStage::Disputed, EscrowError::EscrowDisputed);
+                require!(!stage.is_active(), EscrowError::OrderAlreadyAccepted);
+                require!(stage == EscrowStage::Funded, EscrowError::AlreadyProcessedOrNotFunded);
+            }
+            CompletionAction::Cancelled => {
+                require!(stage != EscrowStage::Disputed, EscrowError::EscrowDisputed);
+                require!(stage.is_active() || stage == EscrowStage::Funded, EscrowError::AlreadyProcessedOrNotFunded);
+            }
+            CompletionAction::OfferRejected | CompletionAction::OfferWithdrawn => {
+                require!(stage == EscrowStage::Offered, EscrowError::NotOffered);
+            }
+            CompletionAction::DisputeReleased | CompletionAction::DisputeRefunded => {
+                require!(stage == EscrowStage::Disputed, EscrowError::NotDisputed);
+            }
+            CompletionAction::Split => {
+                require!(stage.is_active() || stage == EscrowStage::Disputed, EscrowError::AlreadyProcessedOrNotFunded);
+            }
+            _ => {
+                require!(stage != EscrowStage::Disputed, EscrowError::EscrowDisputed);
+                require!(stage != EscrowStage::Funded, EscrowError::NotAccepted);
+                require!(stage.is_active(), EscrowError::AlreadyProcessedOrNotFunded);
+            }
+        }
+        Ok(())
+    }
 }
 
 // --- Errors ---
@@ -332,11 +4117,11 @@ pub enum EscrowStage {
 pub enum EscrowError {
     #[msg("Amount must be greater than zero")]
     ZeroAmount,
-    #[msg("Fee basis points must be between 1 and 1000 (max 10%)")]
+    #[msg("Fee basis points must be at most the configured maximum (max 10%)")]
     InvalidFeeBasisPoints,
-    #[msg("Minimum escrow amount is 0.001 SOL")]
+    #[msg("Escrow amount is below the configured minimum")]
     MinimumAmount,
-    #[msg("Net amount after fee must be at least 0.0005 SOL")]
+    #[msg("Net amount after fee is below the configured minimum")]
     NetAmountTooSmall,
     #[msg("Fee amount is too small")]
     FeeTooSmall,
@@ -346,6 +4131,7 @@ pub enum EscrowError {
     ArithmeticOverflow,
     #[msg("Unauthorized: Only marketplace authority can perform this action")]
     Unauthorized,
+    // No longer returned; kept so the error codes after it stay stable for existing clients
     #[msg("Unauthorized: Invalid authority address")]
     UnauthorizedAuthority,
     #[msg("Escrow not initialized")]
@@ -354,6 +4140,7 @@ pub enum EscrowError {
     AlreadyProcessedOrNotFunded,
     #[msg("Invalid fee wallet address")]
     IncorrectFeeWallet,
+    // No longer returned since the addresses moved into the config account; kept for stable codes
     #[msg("Invalid fee wallet address format")]
     InvalidFeeWalletAddress,
     #[msg("Invalid authority address format")]
@@ -362,4 +4149,104 @@ pub enum EscrowError {
     RecipientNotSeller,
     #[msg("Recipient is not the buyer")]
     RecipientNotBuyer,
+    #[msg("Minimum net amount must be non-zero and not exceed the minimum escrow amount")]
+    InvalidMinimumAmounts,
+    #[msg("Proposed authority is already the marketplace authority")]
+    AuthorityUnchanged,
+    #[msg("No authority transfer is pending")]
+    NoPendingAuthority,
+    #[msg("Signer is not the pending authority")]
+    NotPendingAuthority,
+    #[msg("Escrow retention period cannot be negative")]
+    InvalidRetentionPeriod,
+    #[msg("Escrow has not been released or cancelled")]
+    EscrowNotSettled,
+    #[msg("Escrow is still within its retention period")]
+    RetentionPeriodActive,
+    #[msg("Token escrows require the mint, vault token account and token program")]
+    MissingTokenAccounts,
+    #[msg("Mint does not match the escrow")]
+    MintMismatch,
+    #[msg("Vault token account is not the escrow's associated token account")]
+    InvalidVaultTokenAccount,
+    #[msg("Recipient token account is not owned by the recipient")]
+    InvalidRecipientTokenAccount,
+    #[msg("Token account requires a memo; pass the memo program")]
+    MemoProgramRequired,
+    #[msg("Mints with a permanent delegate are not accepted")]
+    PermanentDelegateNotAllowed,
+    #[msg("Unauthorized: Only the buyer can perform this action")]
+    NotBuyer,
+    #[msg("Unauthorized: Only the seller can perform this action")]
+    NotSeller,
+    #[msg("Deadline periods must be positive")]
+    InvalidDeadlinePeriod,
+    #[msg("Order has already been marked shipped")]
+    AlreadyShipped,
+    #[msg("Order has not been marked shipped")]
+    NotShipped,
+    #[msg("Delivery deadline has passed")]
+    DeliveryDeadlinePassed,
+    #[msg("Deadline has not been reached yet")]
+    DeadlineNotReached,
+    #[msg("Unauthorized: Only the buyer or seller can perform this action")]
+    NotEscrowParty,
+    #[msg("Unauthorized: Only the arbiter can perform this action")]
+    NotArbiter,
+    #[msg("Escrow is not disputed")]
+    NotDisputed,
+    #[msg("Escrow is disputed and can only be settled by the arbiter")]
+    EscrowDisputed,
+    #[msg("You have reached the maximum number of evidence entries for this dispute")]
+    EvidenceLimitReached,
+    #[msg("Seller share exceeds the held amount")]
+    InvalidSplitShare,
+    #[msg("Disputed escrows require the dispute account and its opener")]
+    MissingDisputeAccount,
+    #[msg("Holder discount cannot exceed 100% of the fee")]
+    InvalidDiscount,
+    #[msg("Fee tiers must be ascending by minimum amount and within the tier limit")]
+    InvalidFeeTiers,
+    #[msg("Fee distribution shares must add up to 100%")]
+    InvalidFeeDistribution,
+    #[msg("Fee vault has nothing to distribute")]
+    NothingToDistribute,
+    #[msg("Airdrop needs a non-zero claim period and claimant count within the limit")]
+    InvalidAirdropParams,
+    #[msg("Undistributed airdrop share of the fee vault is too low to fund this airdrop")]
+    InsufficientFeeVaultBalance,
+    #[msg("Invalid Merkle proof for this airdrop claim")]
+    InvalidMerkleProof,
+    #[msg("Airdrop already claimed")]
+    AirdropAlreadyClaimed,
+    #[msg("Airdrop claim period has ended")]
+    AirdropExpired,
+    #[msg("Airdrop claim period has not ended yet")]
+    AirdropNotExpired,
+    #[msg("Airdrop claims exceed the funded total")]
+    AirdropExhausted,
+    #[msg("This instruction is paused")]
+    InstructionPaused,
+    #[msg("Unknown pause flags")]
+    InvalidPauseFlags,
+    #[msg("Listing is sold out")]
+    ListingSoldOut,
+    #[msg("Listing is paused by the seller")]
+    ListingPaused,
+    #[msg("Listing does not have enough units left")]
+    InsufficientListingQuantity,
+    #[msg("Escrow is not an open offer")]
+    NotOffered,
+    #[msg("Seller has not made a counter-offer")]
+    NoCounterOffer,
+    #[msg("Seller has not accepted the order")]
+    NotAccepted,
+    #[msg("Seller has already accepted the order")]
+    OrderAlreadyAccepted,
+    #[msg("Account is not an escrow in the original layout")]
+    InvalidLegacyEscrow,
+    #[msg("The escrow's listing account is required to restock it")]
+    MissingListingAccount,
+    #[msg("Listing does not match the escrow")]
+    ListingMismatch,
 }
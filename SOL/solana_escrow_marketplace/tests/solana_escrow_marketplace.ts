@@ -1,16 +1,1659 @@
 import * as anchor from "@coral-xyz/anchor";
-import { Program } from "@coral-xyz/anchor";
+import { Program, BN } from "@coral-xyz/anchor";
+import {
+  Keypair,
+  LAMPORTS_PER_SOL,
+  PublicKey,
+  SystemProgram,
+  Transaction,
+  TransactionInstruction,
+  sendAndConfirmTransaction,
+} from "@solana/web3.js";
+import {
+  ExtensionType,
+  TOKEN_2022_PROGRAM_ID,
+  TOKEN_PROGRAM_ID,
+  createEnableRequiredMemoTransfersInstruction,
+  createInitializeAccountInstruction,
+  createInitializeMintInstruction,
+  createInitializePermanentDelegateInstruction,
+  createInitializeTransferFeeConfigInstruction,
+  createMint,
+  getAccount,
+  getAccountLen,
+  getAssociatedTokenAddressSync,
+  getMint,
+  getMintLen,
+  getOrCreateAssociatedTokenAccount,
+  getTransferFeeConfig,
+  mintTo,
+} from "@solana/spl-token";
+import { expect } from "chai";
+import { createHash } from "crypto";
 import { SolanaEscrowMarketplace } from "../target/types/solana_escrow_marketplace";
 
+// Run against a local validator: `anchor test --provider.cluster localnet`
 describe("solana_escrow_marketplace", () => {
-  // Configure the client to use the local cluster.
-  anchor.setProvider(anchor.AnchorProvider.env());
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
 
   const program = anchor.workspace.solanaEscrowMarketplace as Program<SolanaEscrowMarketplace>;
+  const connection = provider.connection;
 
-  it("Is initialized!", async () => {
-    // Add your test here.
-    const tx = await program.methods.initialize().rpc();
-    console.log("Your transaction signature", tx);
+  // The provider wallet deploys the program, so it is also the upgrade authority
+  const authority = provider.wallet;
+  const payer = (provider.wallet as anchor.Wallet).payer;
+  const buyer = Keypair.generate();
+  const seller = Keypair.generate();
+  const feeWallet = Keypair.generate();
+
+  // Config defaults: 1% from the buyer on top, 1% from the seller at release
+  const BUYER_FEE_BASIS_POINTS = 100;
+  const SELLER_FEE_BASIS_POINTS = 100;
+  const ESCROW_AMOUNT = new BN(LAMPORTS_PER_SOL);
+  const EXPECTED_BUYER_FEE = ESCROW_AMOUNT.muln(BUYER_FEE_BASIS_POINTS).divn(10_000);
+  const EXPECTED_SELLER_FEE = ESCROW_AMOUNT.muln(SELLER_FEE_BASIS_POINTS).divn(10_000);
+  const EXPECTED_NET = ESCROW_AMOUNT.sub(EXPECTED_SELLER_FEE);
+
+  // Config default deadline periods, restored after tests that shorten them
+  const DELIVERY_PERIOD = 14 * 24 * 60 * 60;
+  const CONFIRMATION_PERIOD = 7 * 24 * 60 * 60;
+
+  const TOKEN_DECIMALS = 6;
+  const TOKEN_AMOUNT = new BN(1_000_000);
+  const MEMO_PROGRAM_ID = new PublicKey("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+
+  const [configPda] = PublicKey.findProgramAddressSync([Buffer.from("config")], program.programId);
+  const [feeVaultPda] = PublicKey.findProgramAddressSync([Buffer.from("fee_vault")], program.programId);
+  const [programDataPda] = PublicKey.findProgramAddressSync(
+    [program.programId.toBuffer()],
+    new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+  );
+
+  const escrowPda = (seed: BN) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("escrow"), buyer.publicKey.toBuffer(), seed.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    )[0];
+  const vaultPda = (escrow: PublicKey) =>
+    PublicKey.findProgramAddressSync([Buffer.from("vault"), escrow.toBuffer()], program.programId)[0];
+
+  const listingPda = (listingId: BN) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("listing"), seller.publicKey.toBuffer(), listingId.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    )[0];
+
+  const orderReference = (seed: BN) =>
+    Array.from(createHash("sha256").update(`listing-${seed.toString()}`).digest());
+
+  const airdrop = async (to: PublicKey, lamports: number) => {
+    const sig = await connection.requestAirdrop(to, lamports);
+    await connection.confirmTransaction(sig, "confirmed");
+  };
+
+  const expectError = async (promise: Promise<unknown>, code: string) => {
+    try {
+      await promise;
+    } catch (err) {
+      expect((err as anchor.AnchorError).error.errorCode.code).to.equal(code);
+      return;
+    }
+    expect.fail(`expected ${code}`);
+  };
+
+  const initializeEscrow = async (seed: BN, optionalAccounts: Record<string, PublicKey> = {}) => {
+    const escrow = escrowPda(seed);
+    await program.methods
+      .initializeEscrow(seed, ESCROW_AMOUNT, orderReference(seed))
+      .accountsPartial({
+        buyer: buyer.publicKey,
+        seller: seller.publicKey,
+        config: configPda,
+        escrowState: escrow,
+        vault: vaultPda(escrow),
+        systemProgram: SystemProgram.programId,
+        ...optionalAccounts,
+      })
+      .signers([buyer])
+      .rpc();
+    return escrow;
+  };
+
+  const acceptOrder = (seed: BN) => {
+    const escrow = escrowPda(seed);
+    return program.methods
+      .acceptOrder(seed)
+      .accountsPartial({
+        caller: seller.publicKey,
+        config: configPda,
+        escrowState: escrow,
+        vault: vaultPda(escrow),
+        recipientAccount: seller.publicKey,
+        feeVault: feeVaultPda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([seller])
+      .rpc();
+  };
+
+  // Funds an escrow and has the seller accept it, so delivery deadlines are running
+  const acceptedEscrow = async (seed: BN, optionalAccounts: Record<string, PublicKey> = {}) => {
+    const escrow = await initializeEscrow(seed, optionalAccounts);
+    await acceptOrder(seed);
+    return escrow;
+  };
+
+  const setPeriods = (delivery: number, confirmation: number) =>
+    program.methods
+      .setDeadlinePeriods(new BN(delivery), new BN(confirmation))
+      .accountsPartial({ authority: authority.publicKey, config: configPda })
+      .rpc();
+
+  // Creates a Token-2022 mint, running `initialize` for its extensions before the mint itself
+  const createMint2022 = async (
+    extensions: ExtensionType[],
+    initialize: (mint: PublicKey) => TransactionInstruction[] = () => []
+  ) => {
+    const mint = Keypair.generate();
+    const space = getMintLen(extensions);
+    const tx = new Transaction().add(
+      SystemProgram.createAccount({
+        fromPubkey: payer.publicKey,
+        newAccountPubkey: mint.publicKey,
+        space,
+        lamports: await connection.getMinimumBalanceForRentExemption(space),
+        programId: TOKEN_2022_PROGRAM_ID,
+      }),
+      ...initialize(mint.publicKey),
+      createInitializeMintInstruction(mint.publicKey, TOKEN_DECIMALS, payer.publicKey, null, TOKEN_2022_PROGRAM_ID)
+    );
+    await sendAndConfirmTransaction(connection, tx, [payer, mint]);
+    return mint.publicKey;
+  };
+
+  // Associated token account of `owner`, created if needed and topped up with `amount`
+  const tokenAccount = async (mint: PublicKey, owner: PublicKey, tokenProgram: PublicKey, amount = 0) => {
+    const account = await getOrCreateAssociatedTokenAccount(
+      connection, payer, mint, owner, true, undefined, undefined, tokenProgram
+    );
+    if (amount > 0) {
+      await mintTo(connection, payer, mint, account.address, payer, amount, [], undefined, tokenProgram);
+    }
+    return account.address;
+  };
+
+  const tokenBalance = async (address: PublicKey, tokenProgram: PublicKey) =>
+    Number((await getAccount(connection, address, undefined, tokenProgram)).amount);
+
+  const vaultTokenAccount = (escrow: PublicKey, mint: PublicKey, tokenProgram: PublicKey) =>
+    getAssociatedTokenAddressSync(mint, escrow, true, tokenProgram);
+
+  const initializeTokenEscrow = async (seed: BN, mint: PublicKey, tokenProgram: PublicKey, amount = TOKEN_AMOUNT) => {
+    const escrow = escrowPda(seed);
+    await program.methods
+      .initializeTokenEscrow(seed, amount, orderReference(seed))
+      .accountsPartial({
+        buyer: buyer.publicKey,
+        seller: seller.publicKey,
+        config: configPda,
+        escrowState: escrow,
+        vault: vaultPda(escrow),
+        mint,
+        buyerTokenAccount: getAssociatedTokenAddressSync(mint, buyer.publicKey, false, tokenProgram),
+        vaultTokenAccount: vaultTokenAccount(escrow, mint, tokenProgram),
+        tokenProgram,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([buyer])
+      .rpc();
+    return escrow;
+  };
+
+  // ProcessEscrow accounts for a token escrow paying `recipient` into `recipientTokenAccount`
+  const tokenSettlement = async (
+    seed: BN,
+    mint: PublicKey,
+    tokenProgram: PublicKey,
+    caller: PublicKey,
+    recipient: PublicKey,
+    recipientTokenAccount: PublicKey
+  ) => {
+    const escrow = escrowPda(seed);
+    return {
+      caller,
+      config: configPda,
+      escrowState: escrow,
+      vault: vaultPda(escrow),
+      recipientAccount: recipient,
+      feeVault: feeVaultPda,
+      mint,
+      vaultTokenAccount: vaultTokenAccount(escrow, mint, tokenProgram),
+      recipientTokenAccount,
+      feeTokenAccount: await tokenAccount(mint, feeVaultPda, tokenProgram),
+      tokenProgram,
+      systemProgram: SystemProgram.programId,
+    };
+  };
+
+  before(async () => {
+    await airdrop(buyer.publicKey, 100 * LAMPORTS_PER_SOL);
+    await airdrop(seller.publicKey, LAMPORTS_PER_SOL);
+
+    await program.methods
+      .initializeConfig(authority.publicKey, feeWallet.publicKey, 1000, new BN(1_000_000), new BN(500_000), new BN(0))
+      .accountsPartial({
+        payer: authority.publicKey,
+        config: configPda,
+        program: program.programId,
+        programData: programDataPda,
+        feeVault: feeVaultPda,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+  });
+
+  it("holds the buyer fee on top in the vault until the seller accepts", async () => {
+    const seed = new BN(1);
+    const feeBefore = await connection.getBalance(feeVaultPda);
+
+    const escrow = await initializeEscrow(seed);
+
+    const rent = await connection.getMinimumBalanceForRentExemption(0);
+    expect(await connection.getBalance(feeVaultPda)).to.equal(feeBefore);
+    expect(await connection.getBalance(vaultPda(escrow))).to.equal(
+      ESCROW_AMOUNT.add(EXPECTED_BUYER_FEE).toNumber() + rent
+    );
+
+    let state = await program.account.escrowState.fetch(escrow);
+    expect(state.amountForSeller.toString()).to.equal(ESCROW_AMOUNT.toString());
+    expect(state.buyerFeeAmount.toString()).to.equal(EXPECTED_BUYER_FEE.toString());
+    expect(state.sellerFeeAmount.toString()).to.equal(EXPECTED_SELLER_FEE.toString());
+    expect(state.orderReference).to.deep.equal(orderReference(seed));
+    expect(state.stage).to.deep.equal({ funded: {} });
+    expect(state.deliveryDeadline.toNumber()).to.equal(0);
+
+    await acceptOrder(seed);
+
+    expect((await connection.getBalance(feeVaultPda)) - feeBefore).to.equal(EXPECTED_BUYER_FEE.toNumber());
+    expect(await connection.getBalance(vaultPda(escrow))).to.equal(ESCROW_AMOUNT.toNumber() + rent);
+    state = await program.account.escrowState.fetch(escrow);
+    expect(state.stage).to.deep.equal({ accepted: {} });
+    expect(state.deliveryDeadline.toNumber()).to.be.greaterThan(0);
+  });
+
+  it("refunds the buyer fee too when the buyer cancels before acceptance", async () => {
+    const seed = new BN(18);
+    const buyerBefore = await connection.getBalance(buyer.publicKey);
+    const escrow = await initializeEscrow(seed);
+    const settlement = {
+      caller: buyer.publicKey,
+      config: configPda,
+      escrowState: escrow,
+      vault: vaultPda(escrow),
+      recipientAccount: buyer.publicKey,
+      feeVault: feeVaultPda,
+      systemProgram: SystemProgram.programId,
+    };
+
+    await expectError(
+      program.methods.confirmReceipt(seed).accountsPartial(settlement).signers([buyer]).rpc(),
+      "NotAccepted"
+    );
+    await program.methods.cancelOrder(seed).accountsPartial(settlement).signers([buyer]).rpc();
+
+    const rent = (await connection.getBalance(escrow)) + (await connection.getBalance(vaultPda(escrow)));
+    expect(buyerBefore - (await connection.getBalance(buyer.publicKey))).to.equal(rent);
+    const state = await program.account.escrowState.fetch(escrow);
+    expect(state.stage).to.deep.equal({ cancelled: {} });
+    expect(state.buyerFeeAmount.toNumber()).to.equal(0);
+  });
+
+  it("releases vault funds to the seller minus the seller fee", async () => {
+    const seed = new BN(1);
+    const escrow = escrowPda(seed);
+    const sellerBefore = await connection.getBalance(seller.publicKey);
+    const feeBefore = await connection.getBalance(feeVaultPda);
+
+    await program.methods
+      .releaseFundsToSeller(seed)
+      .accountsPartial({
+        caller: authority.publicKey,
+        config: configPda,
+        escrowState: escrow,
+        vault: vaultPda(escrow),
+        recipientAccount: seller.publicKey,
+        feeVault: feeVaultPda,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    const sellerAfter = await connection.getBalance(seller.publicKey);
+    expect(sellerAfter - sellerBefore).to.equal(EXPECTED_NET.toNumber());
+    const feeAfter = await connection.getBalance(feeVaultPda);
+    expect(feeAfter - feeBefore).to.equal(EXPECTED_SELLER_FEE.toNumber());
+
+    const state = await program.account.escrowState.fetch(escrow);
+    expect(state.stage).to.deep.equal({ released: {} });
+  });
+
+  it("refunds the buyer from the vault on cancel", async () => {
+    const seed = new BN(2);
+    const escrow = await acceptedEscrow(seed);
+    const buyerBefore = await connection.getBalance(buyer.publicKey);
+
+    await program.methods
+      .cancelEscrowAndRefundBuyer(seed)
+      .accountsPartial({
+        caller: authority.publicKey,
+        config: configPda,
+        escrowState: escrow,
+        vault: vaultPda(escrow),
+        recipientAccount: buyer.publicKey,
+        feeVault: feeVaultPda,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    const buyerAfter = await connection.getBalance(buyer.publicKey);
+    expect(buyerAfter - buyerBefore).to.equal(ESCROW_AMOUNT.toNumber());
+
+    const state = await program.account.escrowState.fetch(escrow);
+    expect(state.stage).to.deep.equal({ cancelled: {} });
+  });
+
+  it("closes a settled escrow and returns rent to the buyer", async () => {
+    const seed = new BN(1);
+    const escrow = escrowPda(seed);
+    const vault = vaultPda(escrow);
+    const rent = (await connection.getBalance(escrow)) + (await connection.getBalance(vault));
+    const buyerBefore = await connection.getBalance(buyer.publicKey);
+
+    await program.methods
+      .closeEscrow(seed)
+      .accountsPartial({
+        caller: authority.publicKey,
+        config: configPda,
+        escrowState: escrow,
+        vault,
+        buyer: buyer.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+    expect(await connection.getBalance(vault)).to.equal(0);
+    expect((await connection.getBalance(buyer.publicKey)) - buyerBefore).to.equal(rent);
+  });
+
+  it("lets the buyer confirm receipt and release to the seller", async () => {
+    const seed = new BN(4);
+    const escrow = await acceptedEscrow(seed);
+    const sellerBefore = await connection.getBalance(seller.publicKey);
+
+    await program.methods
+      .confirmReceipt(seed)
+      .accountsPartial({
+        caller: buyer.publicKey,
+        config: configPda,
+        escrowState: escrow,
+        vault: vaultPda(escrow),
+        recipientAccount: seller.publicKey,
+        feeVault: feeVaultPda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([buyer])
+      .rpc();
+
+    const sellerAfter = await connection.getBalance(seller.publicKey);
+    expect(sellerAfter - sellerBefore).to.equal(EXPECTED_NET.toNumber());
+
+    const state = await program.account.escrowState.fetch(escrow);
+    expect(state.stage).to.deep.equal({ released: {} });
+  });
+
+  it("freezes a disputed escrow until the arbiter resolves it", async () => {
+    const seed = new BN(6);
+    const escrow = await acceptedEscrow(seed);
+    const [dispute] = PublicKey.findProgramAddressSync([Buffer.from("dispute"), escrow.toBuffer()], program.programId);
+    const settlement = {
+      caller: authority.publicKey,
+      config: configPda,
+      escrowState: escrow,
+      vault: vaultPda(escrow),
+      recipientAccount: buyer.publicKey,
+      feeVault: feeVaultPda,
+      systemProgram: SystemProgram.programId,
+    };
+
+    await program.methods
+      .openDispute(seed)
+      .accountsPartial({ party: buyer.publicKey, escrowState: escrow, dispute, systemProgram: SystemProgram.programId })
+      .signers([buyer])
+      .rpc();
+    const submitEvidence = (party: Keypair, fill: number) =>
+      program.methods
+        .submitEvidence(seed, Array.from(Buffer.alloc(32, fill)))
+        .accountsPartial({ party: party.publicKey, escrowState: escrow, dispute })
+        .signers([party])
+        .rpc();
+
+    // Filling the buyer's evidence slots leaves the seller's untouched
+    for (let i = 0; i < 5; i++) {
+      await submitEvidence(buyer, i);
+    }
+    await expectError(submitEvidence(buyer, 5), "EvidenceLimitReached");
+    await submitEvidence(seller, 6);
+    const disputeState = await program.account.dispute.fetch(dispute);
+    expect(disputeState.evidence.length).to.equal(6);
+    expect(disputeState.evidence[5].submitter.equals(seller.publicKey)).to.be.true;
+
+    await expectError(
+      program.methods.cancelEscrowAndRefundBuyer(seed).accountsPartial(settlement).rpc(),
+      "EscrowDisputed"
+    );
+
+    // The authority is the default arbiter
+    await program.methods
+      .resolveDispute(seed, false)
+      .accountsPartial({ settlement, dispute, disputeOpener: buyer.publicKey })
+      .rpc();
+
+    const state = await program.account.escrowState.fetch(escrow);
+    expect(state.stage).to.deep.equal({ cancelled: {} });
+    expect(await connection.getAccountInfo(dispute)).to.be.null;
+  });
+
+  it("splits an escrow between seller and buyer", async () => {
+    const seed = new BN(7);
+    const escrow = await acceptedEscrow(seed);
+    const sellerBefore = await connection.getBalance(seller.publicKey);
+    const buyerBefore = await connection.getBalance(buyer.publicKey);
+    const sellerGross = ESCROW_AMOUNT.muln(6000).divn(10_000);
+    // The seller fee is only charged on the seller's share
+    const sellerShare = sellerGross.sub(EXPECTED_SELLER_FEE.muln(6000).divn(10_000));
+
+    await program.methods
+      .settleSplit(seed, { basisPoints: { 0: 6000 } })
+      .accountsPartial({
+        caller: authority.publicKey,
+        config: configPda,
+        escrowState: escrow,
+        vault: vaultPda(escrow),
+        seller: seller.publicKey,
+        buyer: buyer.publicKey,
+        feeVault: feeVaultPda,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    expect((await connection.getBalance(seller.publicKey)) - sellerBefore).to.equal(sellerShare.toNumber());
+    expect((await connection.getBalance(buyer.publicKey)) - buyerBefore).to.equal(
+      ESCROW_AMOUNT.sub(sellerGross).toNumber()
+    );
+
+    const state = await program.account.escrowState.fetch(escrow);
+    expect(state.stage).to.deep.equal({ split: {} });
+    expect(state.sellerSettledAmount.toString()).to.equal(sellerShare.toString());
+  });
+
+  it("rejects releases from anyone but the marketplace authority", async () => {
+    const seed = new BN(3);
+    const escrow = await acceptedEscrow(seed);
+
+    await expectError(
+      program.methods
+        .releaseFundsToSeller(seed)
+        .accountsPartial({
+          caller: seller.publicKey,
+          config: configPda,
+          escrowState: escrow,
+          vault: vaultPda(escrow),
+          recipientAccount: seller.publicKey,
+          feeVault: feeVaultPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([seller])
+        .rpc(),
+      "Unauthorized"
+    );
+  });
+
+  it("hands marketplace authority over only to the proposed key", async () => {
+    const newAuthority = Keypair.generate();
+    const propose = (from: PublicKey, to: PublicKey) =>
+      program.methods.proposeAuthority(to).accountsPartial({ authority: from, config: configPda });
+    const accept = (signer: Keypair) =>
+      program.methods
+        .acceptAuthority()
+        .accountsPartial({ newAuthority: signer.publicKey, config: configPda })
+        .signers([signer])
+        .rpc();
+
+    await propose(authority.publicKey, newAuthority.publicKey).rpc();
+    expect((await program.account.marketplaceConfig.fetch(configPda)).pendingAuthority.equals(newAuthority.publicKey))
+      .to.be.true;
+    await expectError(accept(seller), "NotPendingAuthority");
+
+    await program.methods
+      .cancelAuthorityProposal()
+      .accountsPartial({ authority: authority.publicKey, config: configPda })
+      .rpc();
+    expect((await program.account.marketplaceConfig.fetch(configPda)).pendingAuthority).to.be.null;
+    await expectError(accept(newAuthority), "NoPendingAuthority");
+
+    await propose(authority.publicKey, newAuthority.publicKey).rpc();
+    await accept(newAuthority);
+    const config = await program.account.marketplaceConfig.fetch(configPda);
+    expect(config.authority.equals(newAuthority.publicKey)).to.be.true;
+    expect(config.pendingAuthority).to.be.null;
+    // The arbiter was still the outgoing authority, so it moves along
+    expect(config.arbiter.equals(newAuthority.publicKey)).to.be.true;
+
+    // The previous authority can no longer release escrows
+    const seed = new BN(31);
+    const escrow = await acceptedEscrow(seed);
+    const release = (caller: PublicKey) =>
+      program.methods.releaseFundsToSeller(seed).accountsPartial({
+        caller,
+        config: configPda,
+        escrowState: escrow,
+        vault: vaultPda(escrow),
+        recipientAccount: seller.publicKey,
+        feeVault: feeVaultPda,
+        systemProgram: SystemProgram.programId,
+      });
+    await expectError(release(authority.publicKey).rpc(), "Unauthorized");
+    await release(newAuthority.publicKey).signers([newAuthority]).rpc();
+
+    // Hand it back for the remaining tests
+    await propose(newAuthority.publicKey, authority.publicKey).signers([newAuthority]).rpc();
+    await program.methods.acceptAuthority().accountsPartial({ newAuthority: authority.publicKey, config: configPda }).rpc();
+    const restored = await program.account.marketplaceConfig.fetch(configPda);
+    expect(restored.authority.equals(authority.publicKey)).to.be.true;
+    expect(restored.arbiter.equals(authority.publicKey)).to.be.true;
+  });
+
+  it("charges the config fee rates rather than anything the client chooses", async () => {
+    const setFeeRates = (buyerBps: number, sellerBps: number) =>
+      program.methods
+        .setFeeRates(buyerBps, sellerBps)
+        .accountsPartial({ authority: authority.publicKey, config: configPda })
+        .rpc();
+
+    await setFeeRates(300, 50);
+    const feeBefore = await connection.getBalance(feeVaultPda);
+    const escrow = await acceptedEscrow(new BN(8));
+    await setFeeRates(BUYER_FEE_BASIS_POINTS, SELLER_FEE_BASIS_POINTS);
+
+    const feeAfter = await connection.getBalance(feeVaultPda);
+    expect(feeAfter - feeBefore).to.equal(ESCROW_AMOUNT.muln(300).divn(10_000).toNumber());
+
+    const state = await program.account.escrowState.fetch(escrow);
+    expect(state.buyerFeeBasisPoints).to.equal(300);
+    expect(state.sellerFeeBasisPoints).to.equal(50);
+    expect(state.sellerFeeAmount.toString()).to.equal(ESCROW_AMOUNT.muln(50).divn(10_000).toString());
+
+    await expectError(
+      program.methods
+        .setFeeRates(1001, SELLER_FEE_BASIS_POINTS)
+        .accountsPartial({ authority: authority.publicKey, config: configPda })
+        .rpc(),
+      "InvalidFeeBasisPoints"
+    );
+  });
+
+  it("applies the highest fee tier the escrow amount reaches", async () => {
+    const [feeSchedule] = PublicKey.findProgramAddressSync([Buffer.from("fee_schedule")], program.programId);
+    const setFeeTiers = (tiers: { minAmount: BN; buyerFeeBasisPoints: number; sellerFeeBasisPoints: number }[]) =>
+      program.methods
+        .setFeeTiers(tiers)
+        .accountsPartial({
+          authority: authority.publicKey,
+          config: configPda,
+          feeSchedule,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+    await setFeeTiers([
+      { minAmount: new BN(LAMPORTS_PER_SOL / 10), buyerFeeBasisPoints: 80, sellerFeeBasisPoints: 80 },
+      { minAmount: ESCROW_AMOUNT, buyerFeeBasisPoints: 50, sellerFeeBasisPoints: 25 },
+    ]);
+    const feeBefore = await connection.getBalance(feeVaultPda);
+    const escrow = await acceptedEscrow(new BN(9), { feeSchedule });
+    await setFeeTiers([]);
+
+    const feeAfter = await connection.getBalance(feeVaultPda);
+    expect(feeAfter - feeBefore).to.equal(ESCROW_AMOUNT.muln(50).divn(10_000).toNumber());
+
+    const state = await program.account.escrowState.fetch(escrow);
+    expect(state.feeTier).to.equal(1);
+    expect(state.sellerFeeBasisPoints).to.equal(25);
+  });
+
+  it("applies a seller fee override without the buyer passing it, until removed", async () => {
+    const [sellerFeeOverride] = PublicKey.findProgramAddressSync(
+      [Buffer.from("seller_fee"), seller.publicKey.toBuffer()],
+      program.programId
+    );
+
+    await expectError(
+      program.methods
+        .setSellerFeeOverride(seller.publicKey, 40, 20)
+        .accountsPartial({
+          authority: seller.publicKey,
+          config: configPda,
+          sellerFeeOverride,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([seller])
+        .rpc(),
+      "Unauthorized"
+    );
+    await program.methods
+      .setSellerFeeOverride(seller.publicKey, 40, 20)
+      .accountsPartial({
+        authority: authority.publicKey,
+        config: configPda,
+        sellerFeeOverride,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+    const feeOverride = await program.account.sellerFeeOverride.fetch(sellerFeeOverride);
+    expect(feeOverride.seller.equals(seller.publicKey)).to.be.true;
+    expect(feeOverride.sellerFeeBasisPoints).to.equal(20);
+
+    // The override account is derived from the seller, so the buyer can't leave it out
+    let state = await program.account.escrowState.fetch(await acceptedEscrow(new BN(21)));
+    expect(state.feeOverrideApplied).to.be.true;
+    expect(state.buyerFeeBasisPoints).to.equal(40);
+    expect(state.sellerFeeBasisPoints).to.equal(20);
+    expect(state.sellerFeeAmount.toString()).to.equal(ESCROW_AMOUNT.muln(20).divn(10_000).toString());
+
+    await program.methods
+      .removeSellerFeeOverride(seller.publicKey)
+      .accountsPartial({ authority: authority.publicKey, config: configPda, sellerFeeOverride })
+      .rpc();
+    expect(await connection.getAccountInfo(sellerFeeOverride)).to.be.null;
+
+    state = await program.account.escrowState.fetch(await acceptedEscrow(new BN(22)));
+    expect(state.feeOverrideApplied).to.be.false;
+    expect(state.buyerFeeBasisPoints).to.equal(BUYER_FEE_BASIS_POINTS);
+    expect(state.sellerFeeBasisPoints).to.equal(SELLER_FEE_BASIS_POINTS);
+  });
+
+  it("distributes the fee vault balance by the configured ratios", async () => {
+    const burnWallet = Keypair.generate().publicKey;
+    const airdropWallet = Keypair.generate().publicKey;
+    const developmentWallet = Keypair.generate().publicKey;
+    const shares = { burnBasisPoints: 1000, airdropBasisPoints: 4000, operationsBasisPoints: 4500, developmentBasisPoints: 500 };
+
+    await expectError(
+      program.methods
+        .setFeeDistribution(burnWallet, airdropWallet, developmentWallet, { ...shares, developmentBasisPoints: 600 })
+        .accountsPartial({ authority: authority.publicKey, config: configPda })
+        .rpc(),
+      "InvalidFeeDistribution"
+    );
+    await program.methods
+      .setFeeDistribution(burnWallet, airdropWallet, developmentWallet, shares)
+      .accountsPartial({ authority: authority.publicKey, config: configPda })
+      .rpc();
+
+    const rent = await connection.getMinimumBalanceForRentExemption(0);
+    const total = (await connection.getBalance(feeVaultPda)) - rent;
+    const operationsBefore = await connection.getBalance(feeWallet.publicKey);
+
+    await program.methods
+      .distributeFees()
+      .accountsPartial({
+        caller: authority.publicKey,
+        config: configPda,
+        feeVault: feeVaultPda,
+        burnWallet,
+        airdropWallet,
+        operationsWallet: feeWallet.publicKey,
+        developmentWallet,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    const burn = Math.floor((total * 1000) / 10_000);
+    const airdrop = Math.floor((total * 4000) / 10_000);
+    const development = Math.floor((total * 500) / 10_000);
+    expect(await connection.getBalance(burnWallet)).to.equal(burn);
+    expect(await connection.getBalance(airdropWallet)).to.equal(airdrop);
+    expect(await connection.getBalance(developmentWallet)).to.equal(development);
+    expect((await connection.getBalance(feeWallet.publicKey)) - operationsBefore).to.equal(
+      total - burn - airdrop - development
+    );
+    expect(await connection.getBalance(feeVaultPda)).to.equal(rent);
+  });
+
+  it("rejects a holder discount above 100% of the fee", async () => {
+    await expectError(
+      program.methods
+        .setHolderDiscount(Keypair.generate().publicKey, new BN(1_000), 10_001)
+        .accountsPartial({ authority: authority.publicKey, config: configPda })
+        .rpc(),
+      "InvalidDiscount"
+    );
+  });
+
+  it("discounts the buyer fee only for the buyer's own $SZ account at the threshold", async () => {
+    const threshold = 1_000;
+    const discountBasisPoints = 5_000;
+    const szMint = await createMint(connection, payer, payer.publicKey, null, TOKEN_DECIMALS);
+    const otherMint = await createMint(connection, payer, payer.publicKey, null, TOKEN_DECIMALS);
+    const setHolderDiscount = (mint: PublicKey | null, minimum: number, discount: number) =>
+      program.methods
+        .setHolderDiscount(mint, new BN(minimum), discount)
+        .accountsPartial({ authority: authority.publicKey, config: configPda })
+        .rpc();
+    await setHolderDiscount(szMint, threshold, discountBasisPoints);
+
+    const buyerHolding = await tokenAccount(szMint, buyer.publicKey, TOKEN_PROGRAM_ID, threshold - 1);
+    const sellerHolding = await tokenAccount(szMint, seller.publicKey, TOKEN_PROGRAM_ID, threshold);
+    const otherMintHolding = await tokenAccount(otherMint, buyer.publicKey, TOKEN_PROGRAM_ID, threshold);
+    const fundWith = async (seed: number, holderTokenAccount: PublicKey) =>
+      program.account.escrowState.fetch(await initializeEscrow(new BN(seed), { holderTokenAccount }));
+
+    // Below the threshold, someone else's account and the wrong mint are all ignored
+    for (const [seed, holding] of [[32, buyerHolding], [33, sellerHolding], [34, otherMintHolding]] as [number, PublicKey][]) {
+      const state = await fundWith(seed, holding);
+      expect(state.feeDiscountApplied).to.be.false;
+      expect(state.buyerFeeBasisPoints).to.equal(BUYER_FEE_BASIS_POINTS);
+      expect(state.buyerFeeAmount.toString()).to.equal(EXPECTED_BUYER_FEE.toString());
+    }
+
+    await mintTo(connection, payer, szMint, buyerHolding, payer, 1);
+    const state = await fundWith(35, buyerHolding);
+    await setHolderDiscount(null, 0, 0);
+
+    const discountedBasisPoints = BUYER_FEE_BASIS_POINTS - (BUYER_FEE_BASIS_POINTS * discountBasisPoints) / 10_000;
+    expect(state.feeDiscountApplied).to.be.true;
+    expect(state.buyerFeeBasisPoints).to.equal(discountedBasisPoints);
+    expect(state.buyerFeeAmount.toString()).to.equal(ESCROW_AMOUNT.muln(discountedBasisPoints).divn(10_000).toString());
+    expect(state.sellerFeeBasisPoints).to.equal(SELLER_FEE_BASIS_POINTS);
+  });
+
+  it("lets only the burn wallet burn $SZ and counts it toward the target", async () => {
+    const burner = Keypair.generate();
+    const shares = { burnBasisPoints: 1000, airdropBasisPoints: 4000, operationsBasisPoints: 4500, developmentBasisPoints: 500 };
+    await program.methods
+      .setFeeDistribution(burner.publicKey, Keypair.generate().publicKey, Keypair.generate().publicKey, shares)
+      .accountsPartial({ authority: authority.publicKey, config: configPda })
+      .rpc();
+    const szMint = await createMint(connection, payer, payer.publicKey, null, TOKEN_DECIMALS);
+    await program.methods
+      .setSzMint(szMint)
+      .accountsPartial({ authority: authority.publicKey, config: configPda })
+      .rpc();
+
+    const burnerTokenAccount = await tokenAccount(szMint, burner.publicKey, TOKEN_PROGRAM_ID, 5_000);
+    const buyerTokenAccount = await tokenAccount(szMint, buyer.publicKey, TOKEN_PROGRAM_ID, 5_000);
+    const burn = (signer: Keypair, from: PublicKey, amount: number) =>
+      program.methods
+        .burnTokens(new BN(amount))
+        .accountsPartial({
+          burner: signer.publicKey,
+          config: configPda,
+          mint: szMint,
+          burnerTokenAccount: from,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([signer])
+        .rpc();
+
+    await expectError(burn(buyer, buyerTokenAccount, 2_000), "Unauthorized");
+
+    const burnedBefore = (await program.account.marketplaceConfig.fetch(configPda)).totalBurned.toNumber();
+    // Burns don't depend on the holder discount being switched on
+    expect((await program.account.marketplaceConfig.fetch(configPda)).discountMint).to.be.null;
+    await burn(burner, burnerTokenAccount, 2_000);
+
+    const config = await program.account.marketplaceConfig.fetch(configPda);
+    expect(config.szMint.equals(szMint)).to.be.true;
+    expect(config.totalBurned.toNumber() - burnedBefore).to.equal(2_000);
+    expect(Number((await getMint(connection, szMint)).supply)).to.equal(8_000);
+    expect(await tokenBalance(burnerTokenAccount, TOKEN_PROGRAM_ID)).to.equal(3_000);
+  });
+
+  it("pays a holder airdrop claim once per Merkle leaf", async () => {
+    // Fund the fee vault with a fresh buyer fee
+    await acceptedEscrow(new BN(10));
+
+    const epoch = new BN(1);
+    const amount = new BN(1_000_000);
+    const index = Buffer.alloc(4);
+    const leaf = createHash("sha256")
+      .update(Buffer.concat([Buffer.from([0]), index, buyer.publicKey.toBuffer(), amount.toArrayLike(Buffer, "le", 8)]))
+      .digest();
+    const [airdropEpoch] = PublicKey.findProgramAddressSync(
+      [Buffer.from("airdrop"), epoch.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const [airdropVault] = PublicKey.findProgramAddressSync(
+      [Buffer.from("airdrop_vault"), airdropEpoch.toBuffer()],
+      program.programId
+    );
+
+    // A single-leaf tree: the leaf is the root and the proof is empty
+    await program.methods
+      .postAirdropEpoch(epoch, Array.from(leaf), amount, 1, new BN(60 * 60))
+      .accountsPartial({
+        authority: authority.publicKey,
+        config: configPda,
+        feeVault: feeVaultPda,
+        airdropEpoch,
+        airdropVault,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    const claim = () =>
+      program.methods
+        .claimAirdrop(epoch, 0, amount, [])
+        .accountsPartial({ claimant: buyer.publicKey, airdropEpoch, airdropVault, systemProgram: SystemProgram.programId })
+        .signers([buyer])
+        .rpc();
+
+    const buyerBefore = await connection.getBalance(buyer.publicKey);
+    await claim();
+    expect((await connection.getBalance(buyer.publicKey)) - buyerBefore).to.equal(amount.toNumber());
+    await expectError(claim(), "AirdropAlreadyClaimed");
+
+    const state = await program.account.airdropEpoch.fetch(airdropEpoch);
+    expect(state.claimedAmount.toString()).to.equal(amount.toString());
+  });
+
+  it("sweeps an airdrop epoch back to the fee vault only after it expires", async () => {
+    // Fund the fee vault with a fresh buyer fee
+    await acceptedEscrow(new BN(36));
+    const amount = new BN(1_000_000);
+    const root = Array.from(createHash("sha256").update("sweep").digest());
+    const airdropAccounts = (epoch: BN) => {
+      const [airdropEpoch] = PublicKey.findProgramAddressSync(
+        [Buffer.from("airdrop"), epoch.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [airdropVault] = PublicKey.findProgramAddressSync(
+        [Buffer.from("airdrop_vault"), airdropEpoch.toBuffer()],
+        program.programId
+      );
+      return {
+        authority: authority.publicKey,
+        config: configPda,
+        feeVault: feeVaultPda,
+        airdropEpoch,
+        airdropVault,
+        systemProgram: SystemProgram.programId,
+      };
+    };
+    const post = (epoch: BN, claimPeriod: number, total = amount) =>
+      program.methods.postAirdropEpoch(epoch, root, total, 1, new BN(claimPeriod)).accountsPartial(airdropAccounts(epoch)).rpc();
+    const sweep = (epoch: BN) => program.methods.sweepAirdrop(epoch).accountsPartial(airdropAccounts(epoch)).rpc();
+
+    // Epochs can't take the burn, operations and development shares of undistributed fees
+    const available = (await connection.getBalance(feeVaultPda)) - (await connection.getMinimumBalanceForRentExemption(0));
+    await expectError(post(new BN(4), 60 * 60, new BN(available)), "InsufficientFeeVaultBalance");
+
+    const drawnBefore = (await program.account.marketplaceConfig.fetch(configPda)).airdropDrawn;
+    const openEpoch = new BN(2);
+    await post(openEpoch, 60 * 60);
+    await expectError(sweep(openEpoch), "AirdropNotExpired");
+    const drawnAfter = (await program.account.marketplaceConfig.fetch(configPda)).airdropDrawn;
+    expect(drawnAfter.sub(drawnBefore).toString()).to.equal(amount.toString());
+
+    const expiredEpoch = new BN(3);
+    await post(expiredEpoch, 1);
+    await new Promise((resolve) => setTimeout(resolve, 3000));
+    const { airdropEpoch, airdropVault } = airdropAccounts(expiredEpoch);
+    const vaultBalance = await connection.getBalance(airdropVault);
+    expect(vaultBalance).to.equal(amount.toNumber() + (await connection.getMinimumBalanceForRentExemption(0)));
+
+    const feeBefore = await connection.getBalance(feeVaultPda);
+    await sweep(expiredEpoch);
+    expect((await connection.getBalance(feeVaultPda)) - feeBefore).to.equal(vaultBalance);
+    expect(await connection.getBalance(airdropVault)).to.equal(0);
+    expect(await connection.getAccountInfo(airdropEpoch)).to.be.null;
+  });
+
+  it("blocks creation and release while paused but still allows refunds", async () => {
+    const setPaused = (flags: number) =>
+      program.methods.setPaused(flags).accountsPartial({ authority: authority.publicKey, config: configPda }).rpc();
+    const seed = new BN(11);
+    const escrow = await acceptedEscrow(seed);
+    const settlement = (recipient: PublicKey) => ({
+      caller: authority.publicKey,
+      config: configPda,
+      escrowState: escrow,
+      vault: vaultPda(escrow),
+      recipientAccount: recipient,
+      feeVault: feeVaultPda,
+      systemProgram: SystemProgram.programId,
+    });
+
+    // PAUSE_CREATE | PAUSE_RELEASE
+    await setPaused(0b011);
+    await expectError(initializeEscrow(new BN(12)), "InstructionPaused");
+    await expectError(
+      program.methods.releaseFundsToSeller(seed).accountsPartial(settlement(seller.publicKey)).rpc(),
+      "InstructionPaused"
+    );
+    await program.methods.cancelEscrowAndRefundBuyer(seed).accountsPartial(settlement(buyer.publicKey)).rpc();
+    await setPaused(0);
+
+    const state = await program.account.escrowState.fetch(escrow);
+    expect(state.stage).to.deep.equal({ cancelled: {} });
+  });
+
+  it("escrows exactly the listing price and decrements inventory on purchase", async () => {
+    const listingId = new BN(1);
+    const listing = listingPda(listingId);
+    await program.methods
+      .createListing(listingId, ESCROW_AMOUNT, null, 2)
+      .accountsPartial({
+        seller: seller.publicKey,
+        config: configPda,
+        listing,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([seller])
+      .rpc();
+
+    const purchase = (seed: BN, quantity: number) => {
+      const escrow = escrowPda(seed);
+      return program.methods
+        .purchaseListing(seed, quantity, orderReference(seed))
+        .accountsPartial({
+          escrow: {
+            buyer: buyer.publicKey,
+            seller: seller.publicKey,
+            config: configPda,
+            escrowState: escrow,
+            vault: vaultPda(escrow),
+            systemProgram: SystemProgram.programId,
+          },
+          listing,
+        })
+        .signers([buyer])
+        .rpc();
+    };
+
+    await purchase(new BN(13), 2);
+    const state = await program.account.escrowState.fetch(escrowPda(new BN(13)));
+    expect(state.amountForSeller.toString()).to.equal(ESCROW_AMOUNT.muln(2).toString());
+
+    const listingState = await program.account.listing.fetch(listing);
+    expect(listingState.quantity).to.equal(0);
+    expect(listingState.status).to.deep.equal({ soldOut: {} });
+    await expectError(purchase(new BN(14), 1), "ListingSoldOut");
+  });
+
+  it("pauses, restocks, reprices and closes a listing", async () => {
+    const listingId = new BN(1);
+    const listing = listingPda(listingId);
+    const manage = { seller: seller.publicKey, config: configPda, listing };
+    const seed = new BN(15);
+    const escrow = escrowPda(seed);
+    const purchase = () =>
+      program.methods
+        .purchaseListing(seed, 1, orderReference(seed))
+        .accountsPartial({
+          escrow: {
+            buyer: buyer.publicKey,
+            seller: seller.publicKey,
+            config: configPda,
+            escrowState: escrow,
+            vault: vaultPda(escrow),
+            systemProgram: SystemProgram.programId,
+          },
+          listing,
+        })
+        .signers([buyer])
+        .rpc();
+
+    await program.methods.restockListing(1).accountsPartial(manage).signers([seller]).rpc();
+    await program.methods.pauseListing(true).accountsPartial(manage).signers([seller]).rpc();
+    await expectError(purchase(), "ListingPaused");
+
+    await program.methods.pauseListing(false).accountsPartial(manage).signers([seller]).rpc();
+    await program.methods.updateListing(ESCROW_AMOUNT.muln(2)).accountsPartial(manage).signers([seller]).rpc();
+    await purchase();
+    const state = await program.account.escrowState.fetch(escrow);
+    expect(state.amountForSeller.toString()).to.equal(ESCROW_AMOUNT.muln(2).toString());
+
+    await program.methods
+      .closeListing()
+      .accountsPartial({ seller: seller.publicKey, listing })
+      .signers([seller])
+      .rpc();
+    expect(await connection.getAccountInfo(listing)).to.be.null;
+  });
+
+  it("restocks the listing when an unaccepted purchase is cancelled", async () => {
+    const listingId = new BN(2);
+    const listing = listingPda(listingId);
+    const seed = new BN(24);
+    const escrow = escrowPda(seed);
+    await program.methods
+      .createListing(listingId, ESCROW_AMOUNT, null, 1)
+      .accountsPartial({
+        seller: seller.publicKey,
+        config: configPda,
+        listing,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([seller])
+      .rpc();
+    await program.methods
+      .purchaseListing(seed, 1, orderReference(seed))
+      .accountsPartial({
+        escrow: {
+          buyer: buyer.publicKey,
+          seller: seller.publicKey,
+          config: configPda,
+          escrowState: escrow,
+          vault: vaultPda(escrow),
+          systemProgram: SystemProgram.programId,
+        },
+        listing,
+      })
+      .signers([buyer])
+      .rpc();
+    let state = await program.account.escrowState.fetch(escrow);
+    expect(state.listing.equals(listing)).to.be.true;
+    expect(state.listingQuantity).to.equal(1);
+    expect((await program.account.listing.fetch(listing)).status).to.deep.equal({ soldOut: {} });
+
+    const settlement = {
+      caller: buyer.publicKey,
+      config: configPda,
+      escrowState: escrow,
+      vault: vaultPda(escrow),
+      recipientAccount: buyer.publicKey,
+      feeVault: feeVaultPda,
+      systemProgram: SystemProgram.programId,
+    };
+    await expectError(
+      program.methods.cancelOrder(seed).accountsPartial(settlement).signers([buyer]).rpc(),
+      "MissingListingAccount"
+    );
+    await program.methods.cancelOrder(seed).accountsPartial({ ...settlement, listing }).signers([buyer]).rpc();
+
+    state = await program.account.escrowState.fetch(escrow);
+    expect(state.stage).to.deep.equal({ cancelled: {} });
+    const listingState = await program.account.listing.fetch(listing);
+    expect(listingState.quantity).to.equal(1);
+    expect(listingState.status).to.deep.equal({ active: {} });
+  });
+
+  it("lets the seller reject an unaccepted purchase with a full refund and restock", async () => {
+    // Reuses the single-unit listing restocked by the cancellation test
+    const listing = listingPda(new BN(2));
+    const seed = new BN(39);
+    const escrow = escrowPda(seed);
+    const buyerBefore = await connection.getBalance(buyer.publicKey);
+    await program.methods
+      .purchaseListing(seed, 1, orderReference(seed))
+      .accountsPartial({
+        escrow: {
+          buyer: buyer.publicKey,
+          seller: seller.publicKey,
+          config: configPda,
+          escrowState: escrow,
+          vault: vaultPda(escrow),
+          systemProgram: SystemProgram.programId,
+        },
+        listing,
+      })
+      .signers([buyer])
+      .rpc();
+    expect((await program.account.listing.fetch(listing)).status).to.deep.equal({ soldOut: {} });
+
+    const reject = (caller: Keypair) =>
+      program.methods
+        .rejectOrder(seed)
+        .accountsPartial({
+          caller: caller.publicKey,
+          config: configPda,
+          escrowState: escrow,
+          vault: vaultPda(escrow),
+          recipientAccount: buyer.publicKey,
+          feeVault: feeVaultPda,
+          systemProgram: SystemProgram.programId,
+          listing,
+        })
+        .signers([caller])
+        .rpc();
+    await expectError(reject(buyer), "NotSeller");
+    await reject(seller);
+
+    // Only the escrow and vault rent stays with the buyer's spending
+    const rent = (await connection.getBalance(escrow)) + (await connection.getBalance(vaultPda(escrow)));
+    expect(buyerBefore - (await connection.getBalance(buyer.publicKey))).to.equal(rent);
+    const state = await program.account.escrowState.fetch(escrow);
+    expect(state.stage).to.deep.equal({ cancelled: {} });
+    expect(state.buyerFeeAmount.toNumber()).to.equal(0);
+    const listingState = await program.account.listing.fetch(listing);
+    expect(listingState.quantity).to.equal(1);
+    expect(listingState.status).to.deep.equal({ active: {} });
+
+    await expectError(reject(seller), "AlreadyProcessedOrNotFunded");
+  });
+
+  it("refunds a rejected offer in full and charges fees only on acceptance", async () => {
+    const makeOffer = async (seed: BN) => {
+      const escrow = escrowPda(seed);
+      await program.methods
+        .makeOffer(seed, ESCROW_AMOUNT, orderReference(seed))
+        .accountsPartial({
+          buyer: buyer.publicKey,
+          seller: seller.publicKey,
+          config: configPda,
+          escrowState: escrow,
+          vault: vaultPda(escrow),
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([buyer])
+        .rpc();
+      return escrow;
+    };
+    const respond = (escrow: PublicKey, caller: Keypair) => ({
+      caller: caller.publicKey,
+      config: configPda,
+      escrowState: escrow,
+      vault: vaultPda(escrow),
+      buyer: buyer.publicKey,
+      feeVault: feeVaultPda,
+      systemProgram: SystemProgram.programId,
+    });
+
+    const rejectedSeed = new BN(16);
+    const feeBefore = await connection.getBalance(feeVaultPda);
+    const buyerBefore = await connection.getBalance(buyer.publicKey);
+    const rejected = await makeOffer(rejectedSeed);
+    expect(await connection.getBalance(feeVaultPda)).to.equal(feeBefore);
+
+    await program.methods.rejectOffer(rejectedSeed).accountsPartial(respond(rejected, seller)).signers([seller]).rpc();
+    const rent = (await connection.getBalance(rejected)) + (await connection.getBalance(vaultPda(rejected)));
+    expect(buyerBefore - (await connection.getBalance(buyer.publicKey))).to.equal(rent);
+    expect((await program.account.escrowState.fetch(rejected)).stage).to.deep.equal({ cancelled: {} });
+
+    const seed = new BN(17);
+    const counterAmount = ESCROW_AMOUNT.muln(2);
+    const escrow = await makeOffer(seed);
+    await program.methods
+      .counterOffer(seed, counterAmount)
+      .accountsPartial(respond(escrow, seller))
+      .signers([seller])
+      .rpc();
+    await program.methods.acceptCounterOffer(seed).accountsPartial(respond(escrow, buyer)).signers([buyer]).rpc();
+
+    expect((await connection.getBalance(feeVaultPda)) - feeBefore).to.equal(
+      counterAmount.muln(BUYER_FEE_BASIS_POINTS).divn(10_000).toNumber()
+    );
+    const state = await program.account.escrowState.fetch(escrow);
+    expect(state.stage).to.deep.equal({ accepted: {} });
+    expect(state.amountForSeller.toString()).to.equal(counterAmount.toString());
+    expect(state.counterAmount.toNumber()).to.equal(0);
+  });
+
+  it("charges a counter-offer at the fee tier its own amount reaches", async () => {
+    const [feeSchedule] = PublicKey.findProgramAddressSync([Buffer.from("fee_schedule")], program.programId);
+    const setFeeTiers = (tiers: { minAmount: BN; buyerFeeBasisPoints: number; sellerFeeBasisPoints: number }[]) =>
+      program.methods
+        .setFeeTiers(tiers)
+        .accountsPartial({
+          authority: authority.publicKey,
+          config: configPda,
+          feeSchedule,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+    await setFeeTiers([{ minAmount: ESCROW_AMOUNT, buyerFeeBasisPoints: 50, sellerFeeBasisPoints: 25 }]);
+
+    // A large offer reaches the cheap tier, but the smaller counter must not keep it
+    const seed = new BN(23);
+    const escrow = escrowPda(seed);
+    const respond = (caller: Keypair) => ({
+      caller: caller.publicKey,
+      config: configPda,
+      escrowState: escrow,
+      vault: vaultPda(escrow),
+      buyer: buyer.publicKey,
+      feeVault: feeVaultPda,
+      systemProgram: SystemProgram.programId,
+    });
+    await program.methods
+      .makeOffer(seed, ESCROW_AMOUNT, orderReference(seed))
+      .accountsPartial({
+        buyer: buyer.publicKey,
+        seller: seller.publicKey,
+        config: configPda,
+        escrowState: escrow,
+        vault: vaultPda(escrow),
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([buyer])
+      .rpc();
+    expect((await program.account.escrowState.fetch(escrow)).feeTier).to.equal(0);
+
+    const counterAmount = ESCROW_AMOUNT.divn(2);
+    await program.methods.counterOffer(seed, counterAmount).accountsPartial(respond(seller)).signers([seller]).rpc();
+    const feeBefore = await connection.getBalance(feeVaultPda);
+    await program.methods.acceptCounterOffer(seed).accountsPartial(respond(buyer)).signers([buyer]).rpc();
+    await setFeeTiers([]);
+
+    expect((await connection.getBalance(feeVaultPda)) - feeBefore).to.equal(
+      counterAmount.muln(BUYER_FEE_BASIS_POINTS).divn(10_000).toNumber()
+    );
+    const state = await program.account.escrowState.fetch(escrow);
+    expect(state.feeTier).to.be.null;
+    expect(state.buyerFeeBasisPoints).to.equal(BUYER_FEE_BASIS_POINTS);
+    expect(state.sellerFeeBasisPoints).to.equal(SELLER_FEE_BASIS_POINTS);
+    expect(state.sellerFeeAmount.toString()).to.equal(counterAmount.muln(SELLER_FEE_BASIS_POINTS).divn(10_000).toString());
+  });
+
+  it("records shipment tracking and restarts the confirmation window when shipped", async () => {
+    const seed = new BN(19);
+    const escrow = await acceptedEscrow(seed);
+    const trackingHash = Array.from(createHash("sha256").update("1Z999AA10123456784").digest());
+    const carrierCode = 3;
+
+    await program.methods
+      .markShipped(seed, trackingHash, carrierCode)
+      .accountsPartial({ seller: seller.publicKey, config: configPda, escrowState: escrow })
+      .signers([seller])
+      .rpc();
+
+    const state = await program.account.escrowState.fetch(escrow);
+    expect(state.stage).to.deep.equal({ shipped: {} });
+    expect(state.trackingHash).to.deep.equal(trackingHash);
+    expect(state.carrierCode).to.equal(carrierCode);
+    const config = await program.account.marketplaceConfig.fetch(configPda);
+    expect(state.confirmationDeadline.toNumber()).to.equal(
+      state.shippedAt.toNumber() + config.confirmationPeriod.toNumber()
+    );
+
+    await program.methods
+      .confirmReceipt(seed)
+      .accountsPartial({
+        caller: buyer.publicKey,
+        config: configPda,
+        escrowState: escrow,
+        vault: vaultPda(escrow),
+        recipientAccount: seller.publicKey,
+        feeVault: feeVaultPda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([buyer])
+      .rpc();
+    expect((await program.account.escrowState.fetch(escrow)).stage).to.deep.equal({ released: {} });
+  });
+
+  it("migrates a funded escrow from the original seed-only layout", async () => {
+    // Loaded from tests/fixtures/legacy_escrow.json: 1 SOL escrowed, 0.01 SOL fee already taken
+    const seed = new BN(20);
+    const legacyBuyer = new PublicKey("29d2S7vB453rNYFdR5Ycwt7y9haRT5fwVwL9zTmBhfV2");
+    const legacySeller = new PublicKey("3JF3sEqM796hk5WFqA6EtmEwJQ9quALszsfJyvXNQKy3");
+    const legacyHeld = 990_000_000;
+    const [legacyEscrow] = PublicKey.findProgramAddressSync(
+      [Buffer.from("escrow"), seed.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const [escrow] = PublicKey.findProgramAddressSync(
+      [Buffer.from("escrow"), legacyBuyer.toBuffer(), seed.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+
+    await program.methods
+      .migrateEscrow(seed)
+      .accountsPartial({
+        caller: authority.publicKey,
+        config: configPda,
+        legacyEscrow,
+        buyer: legacyBuyer,
+        escrowState: escrow,
+        vault: vaultPda(escrow),
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    expect(await connection.getAccountInfo(legacyEscrow)).to.be.null;
+    const rent = await connection.getMinimumBalanceForRentExemption(0);
+    expect(await connection.getBalance(vaultPda(escrow))).to.equal(legacyHeld + rent);
+    const state = await program.account.escrowState.fetch(escrow);
+    expect(state.stage).to.deep.equal({ accepted: {} });
+    expect(state.buyer.equals(legacyBuyer)).to.be.true;
+    expect(state.amountForSeller.toNumber()).to.equal(legacyHeld);
+    expect(state.buyerFeeAmount.toNumber()).to.equal(10_000_000);
+    expect(state.deliveryDeadline.toNumber()).to.be.greaterThan(0);
+
+    // The fee was taken at funding, so the seller receives everything held
+    const sellerBefore = await connection.getBalance(legacySeller);
+    await program.methods
+      .releaseFundsToSeller(seed)
+      .accountsPartial({
+        caller: authority.publicKey,
+        config: configPda,
+        escrowState: escrow,
+        vault: vaultPda(escrow),
+        recipientAccount: legacySeller,
+        feeVault: feeVaultPda,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+    expect((await connection.getBalance(legacySeller)) - sellerBefore).to.equal(legacyHeld);
+  });
+
+  it("escrows SPL tokens through accept and release", async () => {
+    const mint = await createMint(connection, payer, payer.publicKey, null, TOKEN_DECIMALS);
+    await tokenAccount(mint, buyer.publicKey, TOKEN_PROGRAM_ID, 10 * TOKEN_AMOUNT.toNumber());
+    const sellerTokenAccount = await tokenAccount(mint, seller.publicKey, TOKEN_PROGRAM_ID);
+    const feeTokenAccount = await tokenAccount(mint, feeVaultPda, TOKEN_PROGRAM_ID);
+    const buyerFee = TOKEN_AMOUNT.muln(BUYER_FEE_BASIS_POINTS).divn(10_000).toNumber();
+    const sellerFee = TOKEN_AMOUNT.muln(SELLER_FEE_BASIS_POINTS).divn(10_000).toNumber();
+
+    const seed = new BN(28);
+    const escrow = await initializeTokenEscrow(seed, mint, TOKEN_PROGRAM_ID);
+    const vault = vaultTokenAccount(escrow, mint, TOKEN_PROGRAM_ID);
+    expect(await tokenBalance(vault, TOKEN_PROGRAM_ID)).to.equal(TOKEN_AMOUNT.toNumber() + buyerFee);
+    expect(await tokenBalance(feeTokenAccount, TOKEN_PROGRAM_ID)).to.equal(0);
+
+    await program.methods
+      .acceptOrder(seed)
+      .accountsPartial(
+        await tokenSettlement(seed, mint, TOKEN_PROGRAM_ID, seller.publicKey, seller.publicKey, sellerTokenAccount)
+      )
+      .signers([seller])
+      .rpc();
+    expect(await tokenBalance(vault, TOKEN_PROGRAM_ID)).to.equal(TOKEN_AMOUNT.toNumber());
+    expect(await tokenBalance(feeTokenAccount, TOKEN_PROGRAM_ID)).to.equal(buyerFee);
+
+    await program.methods
+      .releaseFundsToSeller(seed)
+      .accountsPartial(
+        await tokenSettlement(seed, mint, TOKEN_PROGRAM_ID, authority.publicKey, seller.publicKey, sellerTokenAccount)
+      )
+      .rpc();
+    expect(await tokenBalance(vault, TOKEN_PROGRAM_ID)).to.equal(0);
+    expect(await tokenBalance(sellerTokenAccount, TOKEN_PROGRAM_ID)).to.equal(TOKEN_AMOUNT.toNumber() - sellerFee);
+    expect(await tokenBalance(feeTokenAccount, TOKEN_PROGRAM_ID)).to.equal(buyerFee + sellerFee);
+    const state = await program.account.escrowState.fetch(escrow);
+    expect(state.mint.equals(mint)).to.be.true;
+    expect(state.stage).to.deep.equal({ released: {} });
+  });
+
+  it("refunds SPL tokens to the buyer on cancel, keeping the charged buyer fee", async () => {
+    const mint = await createMint(connection, payer, payer.publicKey, null, TOKEN_DECIMALS);
+    const buyerTokenAccount = await tokenAccount(mint, buyer.publicKey, TOKEN_PROGRAM_ID, 10 * TOKEN_AMOUNT.toNumber());
+    const feeTokenAccount = await tokenAccount(mint, feeVaultPda, TOKEN_PROGRAM_ID);
+    const buyerFee = TOKEN_AMOUNT.muln(BUYER_FEE_BASIS_POINTS).divn(10_000).toNumber();
+    const buyerBefore = await tokenBalance(buyerTokenAccount, TOKEN_PROGRAM_ID);
+
+    const seed = new BN(29);
+    const escrow = await initializeTokenEscrow(seed, mint, TOKEN_PROGRAM_ID);
+    await program.methods
+      .acceptOrder(seed)
+      .accountsPartial(
+        await tokenSettlement(seed, mint, TOKEN_PROGRAM_ID, seller.publicKey, seller.publicKey, buyerTokenAccount)
+      )
+      .signers([seller])
+      .rpc();
+    await program.methods
+      .cancelEscrowAndRefundBuyer(seed)
+      .accountsPartial(
+        await tokenSettlement(seed, mint, TOKEN_PROGRAM_ID, authority.publicKey, buyer.publicKey, buyerTokenAccount)
+      )
+      .rpc();
+
+    expect(await tokenBalance(vaultTokenAccount(escrow, mint, TOKEN_PROGRAM_ID), TOKEN_PROGRAM_ID)).to.equal(0);
+    expect(buyerBefore - (await tokenBalance(buyerTokenAccount, TOKEN_PROGRAM_ID))).to.equal(buyerFee);
+    expect(await tokenBalance(feeTokenAccount, TOKEN_PROGRAM_ID)).to.equal(buyerFee);
+    const state = await program.account.escrowState.fetch(escrow);
+    expect(state.stage).to.deep.equal({ cancelled: {} });
+    expect(state.buyerRefundedAmount.toNumber()).to.equal(TOKEN_AMOUNT.toNumber());
+  });
+
+  it("escrows the token listing price on a token listing purchase", async () => {
+    const mint = await createMint(connection, payer, payer.publicKey, null, TOKEN_DECIMALS);
+    await tokenAccount(mint, buyer.publicKey, TOKEN_PROGRAM_ID, 10 * TOKEN_AMOUNT.toNumber());
+    const listingId = new BN(3);
+    const listing = listingPda(listingId);
+    const price = TOKEN_AMOUNT.divn(2);
+    await program.methods
+      .createListing(listingId, price, mint, 2)
+      .accountsPartial({
+        seller: seller.publicKey,
+        config: configPda,
+        listing,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([seller])
+      .rpc();
+
+    const seed = new BN(30);
+    const escrow = escrowPda(seed);
+    await program.methods
+      .purchaseTokenListing(seed, 2, orderReference(seed))
+      .accountsPartial({
+        escrow: {
+          buyer: buyer.publicKey,
+          seller: seller.publicKey,
+          config: configPda,
+          escrowState: escrow,
+          vault: vaultPda(escrow),
+          mint,
+          buyerTokenAccount: getAssociatedTokenAddressSync(mint, buyer.publicKey, false, TOKEN_PROGRAM_ID),
+          vaultTokenAccount: vaultTokenAccount(escrow, mint, TOKEN_PROGRAM_ID),
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        },
+        listing,
+      })
+      .signers([buyer])
+      .rpc();
+
+    const state = await program.account.escrowState.fetch(escrow);
+    expect(state.amountForSeller.toString()).to.equal(price.muln(2).toString());
+    expect(state.listing.equals(listing)).to.be.true;
+    const buyerFee = price.muln(2).muln(BUYER_FEE_BASIS_POINTS).divn(10_000).toNumber();
+    expect(await tokenBalance(vaultTokenAccount(escrow, mint, TOKEN_PROGRAM_ID), TOKEN_PROGRAM_ID)).to.equal(
+      price.muln(2).toNumber() + buyerFee
+    );
+    const listingState = await program.account.listing.fetch(listing);
+    expect(listingState.quantity).to.equal(0);
+    expect(listingState.status).to.deep.equal({ soldOut: {} });
+  });
+
+  it("holds what a transfer-fee mint delivered and harvests withheld fees before closing", async () => {
+    const transferFeeBasisPoints = 100;
+    const mint = await createMint2022([ExtensionType.TransferFeeConfig], (mint) => [
+      createInitializeTransferFeeConfigInstruction(
+        mint, payer.publicKey, payer.publicKey, transferFeeBasisPoints, BigInt(1_000_000_000), TOKEN_2022_PROGRAM_ID
+      ),
+    ]);
+    const buyerTokenAccount = await tokenAccount(mint, buyer.publicKey, TOKEN_2022_PROGRAM_ID, 10 * TOKEN_AMOUNT.toNumber());
+    const withheld = (amount: number) => Math.ceil((amount * transferFeeBasisPoints) / 10_000);
+
+    const seed = new BN(25);
+    const escrow = await initializeTokenEscrow(seed, mint, TOKEN_2022_PROGRAM_ID);
+    const vault = vaultTokenAccount(escrow, mint, TOKEN_2022_PROGRAM_ID);
+
+    // Both the deposit and the buyer fee lose the transfer fee on the way in
+    const buyerFee = TOKEN_AMOUNT.muln(BUYER_FEE_BASIS_POINTS).divn(10_000).toNumber();
+    const received = TOKEN_AMOUNT.toNumber() - withheld(TOKEN_AMOUNT.toNumber());
+    const receivedFee = buyerFee - withheld(buyerFee);
+    let state = await program.account.escrowState.fetch(escrow);
+    expect(state.amountForSeller.toNumber()).to.equal(received);
+    expect(state.buyerFeeAmount.toNumber()).to.equal(receivedFee);
+    expect(state.sellerFeeAmount.toNumber()).to.equal(Math.floor((received * SELLER_FEE_BASIS_POINTS) / 10_000));
+    expect(await tokenBalance(vault, TOKEN_2022_PROGRAM_ID)).to.equal(received + receivedFee);
+
+    const settlement = await tokenSettlement(
+      seed, mint, TOKEN_2022_PROGRAM_ID, buyer.publicKey, buyer.publicKey, buyerTokenAccount
+    );
+    await program.methods.cancelOrder(seed).accountsPartial(settlement).signers([buyer]).rpc();
+    expect(await tokenBalance(vault, TOKEN_2022_PROGRAM_ID)).to.equal(0);
+    state = await program.account.escrowState.fetch(escrow);
+    expect(state.buyerRefundedAmount.toNumber()).to.equal(received + receivedFee);
+
+    // The emptied vault still holds withheld fees, which must go to the mint before it can close
+    await program.methods
+      .closeEscrow(seed)
+      .accountsPartial({
+        caller: authority.publicKey,
+        config: configPda,
+        escrowState: escrow,
+        vault: vaultPda(escrow),
+        buyer: buyer.publicKey,
+        mint,
+        vaultTokenAccount: vault,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+    expect(await connection.getAccountInfo(vault)).to.be.null;
+    const feeConfig = getTransferFeeConfig(await getMint(connection, mint, undefined, TOKEN_2022_PROGRAM_ID));
+    expect(Number(feeConfig.withheldAmount)).to.be.greaterThan(0);
+  });
+
+  it("pays a memo-required recipient only when the memo program is passed", async () => {
+    const mint = await createMint2022([]);
+    await tokenAccount(mint, buyer.publicKey, TOKEN_2022_PROGRAM_ID, 10 * TOKEN_AMOUNT.toNumber());
+    const memoAccount = Keypair.generate();
+    const space = getAccountLen([ExtensionType.MemoTransfer]);
+    await sendAndConfirmTransaction(
+      connection,
+      new Transaction().add(
+        SystemProgram.createAccount({
+          fromPubkey: payer.publicKey,
+          newAccountPubkey: memoAccount.publicKey,
+          space,
+          lamports: await connection.getMinimumBalanceForRentExemption(space),
+          programId: TOKEN_2022_PROGRAM_ID,
+        }),
+        createInitializeAccountInstruction(memoAccount.publicKey, mint, seller.publicKey, TOKEN_2022_PROGRAM_ID),
+        createEnableRequiredMemoTransfersInstruction(memoAccount.publicKey, seller.publicKey, [], TOKEN_2022_PROGRAM_ID)
+      ),
+      [payer, memoAccount, seller]
+    );
+
+    const seed = new BN(26);
+    await initializeTokenEscrow(seed, mint, TOKEN_2022_PROGRAM_ID);
+    await program.methods
+      .acceptOrder(seed)
+      .accountsPartial(
+        await tokenSettlement(seed, mint, TOKEN_2022_PROGRAM_ID, seller.publicKey, seller.publicKey, memoAccount.publicKey)
+      )
+      .signers([seller])
+      .rpc();
+
+    const settlement = await tokenSettlement(
+      seed, mint, TOKEN_2022_PROGRAM_ID, authority.publicKey, seller.publicKey, memoAccount.publicKey
+    );
+    await expectError(
+      program.methods.releaseFundsToSeller(seed).accountsPartial({ ...settlement, memoProgram: null }).rpc(),
+      "MemoProgramRequired"
+    );
+    await program.methods
+      .releaseFundsToSeller(seed)
+      .accountsPartial({ ...settlement, memoProgram: MEMO_PROGRAM_ID })
+      .rpc();
+    expect(await tokenBalance(memoAccount.publicKey, TOKEN_2022_PROGRAM_ID)).to.equal(
+      TOKEN_AMOUNT.toNumber() - TOKEN_AMOUNT.muln(SELLER_FEE_BASIS_POINTS).divn(10_000).toNumber()
+    );
+  });
+
+  it("rejects mints with a permanent delegate", async () => {
+    const mint = await createMint2022([ExtensionType.PermanentDelegate], (mint) => [
+      createInitializePermanentDelegateInstruction(mint, payer.publicKey, TOKEN_2022_PROGRAM_ID),
+    ]);
+    await tokenAccount(mint, buyer.publicKey, TOKEN_2022_PROGRAM_ID, TOKEN_AMOUNT.toNumber());
+
+    await expectError(initializeTokenEscrow(new BN(27), mint, TOKEN_2022_PROGRAM_ID), "PermanentDelegateNotAllowed");
+  });
+
+  it("lets anyone release to the seller once the confirmation window lapses after shipping", async () => {
+    const stranger = Keypair.generate();
+    const trackingHash = Array.from(createHash("sha256").update("auto-release").digest());
+    const shippedEscrow = async (seed: BN) => {
+      const escrow = await acceptedEscrow(seed);
+      await program.methods
+        .markShipped(seed, trackingHash, 1)
+        .accountsPartial({ seller: seller.publicKey, config: configPda, escrowState: escrow })
+        .signers([seller])
+        .rpc();
+      return escrow;
+    };
+    const autoRelease = (seed: BN, escrow: PublicKey) =>
+      program.methods
+        .autoReleaseToSeller(seed)
+        .accountsPartial({
+          caller: stranger.publicKey,
+          config: configPda,
+          escrowState: escrow,
+          vault: vaultPda(escrow),
+          recipientAccount: seller.publicKey,
+          feeVault: feeVaultPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([stranger])
+        .rpc();
+
+    const openSeed = new BN(37);
+    await expectError(autoRelease(openSeed, await shippedEscrow(openSeed)), "DeadlineNotReached");
+
+    // Only the confirmation window is shortened, so shipping still lands well inside the delivery deadline
+    await setPeriods(DELIVERY_PERIOD, 1);
+    try {
+      const seed = new BN(38);
+      const escrow = await shippedEscrow(seed);
+      await new Promise((resolve) => setTimeout(resolve, 3000));
+
+      const sellerBefore = await connection.getBalance(seller.publicKey);
+      await autoRelease(seed, escrow);
+
+      expect((await connection.getBalance(seller.publicKey)) - sellerBefore).to.equal(EXPECTED_NET.toNumber());
+      const state = await program.account.escrowState.fetch(escrow);
+      expect(state.stage).to.deep.equal({ released: {} });
+    } finally {
+      await setPeriods(DELIVERY_PERIOD, CONFIRMATION_PERIOD);
+    }
+  });
+
+  it("lets anyone refund the buyer once the delivery deadline passes unshipped", async () => {
+    await setPeriods(1, CONFIRMATION_PERIOD);
+    try {
+      const seed = new BN(5);
+      const escrow = await acceptedEscrow(seed);
+      await new Promise((resolve) => setTimeout(resolve, 3000));
+
+      // Signed by an unrelated key: the deadline alone authorizes the refund
+      const stranger = Keypair.generate();
+      const buyerBefore = await connection.getBalance(buyer.publicKey);
+      await program.methods
+        .autoRefundBuyer(seed)
+        .accountsPartial({
+          caller: stranger.publicKey,
+          config: configPda,
+          escrowState: escrow,
+          vault: vaultPda(escrow),
+          recipientAccount: buyer.publicKey,
+          feeVault: feeVaultPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([stranger])
+        .rpc();
+
+      expect((await connection.getBalance(buyer.publicKey)) - buyerBefore).to.equal(ESCROW_AMOUNT.toNumber());
+      const state = await program.account.escrowState.fetch(escrow);
+      expect(state.stage).to.deep.equal({ cancelled: {} });
+    } finally {
+      await setPeriods(DELIVERY_PERIOD, CONFIRMATION_PERIOD);
+    }
   });
 });
@@ -42,11 +42,11 @@ export function generateTransactionSeed(): BN {
   return new BN(Date.now() + Math.floor(Math.random() * 1000000));
 }
 
-// Derive escrow PDA from transaction seed
-export function deriveEscrowPDA(transactionSeed: BN): [PublicKey, number] {
+// Derive escrow PDA from the buyer and transaction seed
+export function deriveEscrowPDA(buyer: PublicKey, transactionSeed: BN): [PublicKey, number] {
   const seedBuffer = transactionSeed.toArrayLike(Buffer, "le", 8);
   return PublicKey.findProgramAddressSync(
-    [Buffer.from("escrow"), seedBuffer],
+    [Buffer.from("escrow"), buyer.toBuffer(), seedBuffer],
     PROGRAM_ID
   );
 }
@@ -61,7 +61,7 @@ export async function createEscrowTransaction(params: EscrowParams): Promise<Esc
   
   // Generate transaction seed and derive PDA
   const transactionSeed = generateTransactionSeed();
-  const [escrowPDA, bump] = deriveEscrowPDA(transactionSeed);
+  const [escrowPDA, bump] = deriveEscrowPDA(buyer, transactionSeed);
   
   // For now, create a simple SOL transfer to the escrow PDA as placeholder
   // In production, you'd build the actual program instruction
@@ -89,10 +89,11 @@ export async function createEscrowTransaction(params: EscrowParams): Promise<Esc
 // Create release funds transaction (authority only)
 export async function createReleaseFundsTransaction(
   transactionSeed: string,
+  buyerWallet: string,
   sellerWallet: string
 ): Promise<Transaction> {
   const seedBN = new BN(transactionSeed);
-  const [escrowPDA] = deriveEscrowPDA(seedBN);
+  const [escrowPDA] = deriveEscrowPDA(new PublicKey(buyerWallet), seedBN);
   const seller = new PublicKey(sellerWallet);
   
   // For now, create a simple SOL transfer from escrow PDA to seller as placeholder
@@ -118,8 +119,8 @@ export async function createCancelEscrowTransaction(
   buyerWallet: string
 ): Promise<Transaction> {
   const seedBN = new BN(transactionSeed);
-  const [escrowPDA] = deriveEscrowPDA(seedBN);
   const buyer = new PublicKey(buyerWallet);
+  const [escrowPDA] = deriveEscrowPDA(buyer, seedBN);
   
   // For now, create a simple SOL transfer from escrow PDA to buyer as placeholder
   // In production, you'd build the actual program instruction
@@ -139,9 +140,9 @@ export async function createCancelEscrowTransaction(
 }
 
 // Get escrow account data
-export async function getEscrowAccount(transactionSeed: string) {
+export async function getEscrowAccount(transactionSeed: string, buyerWallet: string) {
   const seedBN = new BN(transactionSeed);
-  const [escrowPDA] = deriveEscrowPDA(seedBN);
+  const [escrowPDA] = deriveEscrowPDA(new PublicKey(buyerWallet), seedBN);
   
   try {
     const accountInfo = await connection.getAccountInfo(escrowPDA);